@@ -0,0 +1,91 @@
+# pterm shell integration for bash, source it from ~/.bashrc
+
+__pterm_seq=0
+# tells pterm this shell's command lines from anything else's output; not for
+# the programs run from here
+__pterm_token=$PTERM_TOKEN
+unset PTERM_TOKEN
+
+__pterm_emit() {
+    __pterm_seq=$((__pterm_seq + 1))
+    printf '\033]6;file://pterm/%s/%s\007' "$__pterm_seq" "$1"
+}
+
+__pterm_encode() {
+    local LC_ALL=C s=$1 out= c i
+    for ((i = 0; i < ${#s}; i++)); do
+        c=${s:i:1}
+        case $c in
+            [a-zA-Z0-9._~-]) out+=$c ;;
+            *) printf -v c '%%%02X' "'$c"; out+=$c ;;
+        esac
+    done
+    printf '%s' "$out"
+}
+
+# the history number of the last command reported
+__pterm_histnum=$(HISTTIMEFORMAT= builtin history 1)
+__pterm_histnum=${__pterm_histnum#"${__pterm_histnum%%[![:space:]]*}"}
+__pterm_histnum=${__pterm_histnum%%[[:space:]]*}
+
+# sets __pterm_line to the whole line just run, $BASH_COMMAND is only its
+# first command; empty if history didn't get a new entry (an empty line,
+# ignorespace and the like). Not in a subshell, it keeps __pterm_histnum.
+__pterm_command() {
+    local entry num
+    __pterm_line=
+    entry=$(HISTTIMEFORMAT= builtin history 1)
+    entry=${entry#"${entry%%[![:space:]]*}"}
+    num=${entry%%[[:space:]]*}
+    [ -n "$num" ] && [ "$num" != "$__pterm_histnum" ] || return
+    __pterm_histnum=$num
+    entry=${entry#"$num"}
+    __pterm_line=${entry#"${entry%%[![:space:]]*}"}
+}
+
+__pterm_preexec() {
+    # only the first command after the prompt, not everything it runs
+    [ -n "$__pterm_ready" ] || return
+    __pterm_ready=
+    __pterm_emit "preexec/$(__pterm_encode "$BASH_COMMAND")"
+}
+
+# the virtualenv, git branch and AWS profile, for {venv} and co in titles
+__pterm_facts() {
+    local branch
+    branch=$(git symbolic-ref --short -q HEAD 2>/dev/null)
+    printf 'venv=%s&branch=%s&aws_profile=%s' \
+        "$(__pterm_encode "${VIRTUAL_ENV##*/}")" \
+        "$(__pterm_encode "$branch")" \
+        "$(__pterm_encode "$AWS_PROFILE")"
+}
+
+__pterm_precmd() {
+    local ret=$?
+    __pterm_command
+    __pterm_emit "precmd/$ret/$(__pterm_facts)&command=$(__pterm_encode "$__pterm_line")&token=$__pterm_token"
+}
+
+# a DEBUG trap set before this file was sourced can't be seen from in here, so
+# ours goes in at the first prompt, from a function with the trace attribute
+# that shares the trap. With bash-preexec loaded it owns the DEBUG trap,
+# otherwise one that was already there keeps running, before ours.
+__pterm_hook() {
+    [ -z "$__pterm_hooked" ] || return 0
+    __pterm_hooked=1
+    if [ -n "${bash_preexec_imported:-}${__bp_imported:-}" ]; then
+        preexec_functions+=(__pterm_preexec)
+        return 0
+    fi
+    __pterm_trap_command() { __pterm_previous_debug=$3; }
+    eval "__pterm_trap_command $(trap -p DEBUG)"
+    unset -f __pterm_trap_command
+    trap 'eval "${__pterm_previous_debug:-:}"; __pterm_preexec' DEBUG
+}
+declare -ft __pterm_hook
+
+# first, to see the command's status; bash 5.1 also takes an array
+case $(declare -p PROMPT_COMMAND 2>/dev/null) in
+    "declare -a"*) PROMPT_COMMAND=(__pterm_precmd "${PROMPT_COMMAND[@]}" __pterm_hook __pterm_ready=1) ;;
+    *) PROMPT_COMMAND="__pterm_precmd;${PROMPT_COMMAND:+$PROMPT_COMMAND;}__pterm_hook;__pterm_ready=1" ;;
+esac
@@ -0,0 +1,27 @@
+// screen readers: gtk names most widgets through atk by itself, but not
+// icon-only tabs or terminals, and nothing is said on a tab switch or a bell
+
+use gtk::atk::prelude::*;
+use gtk::prelude::*;
+
+pub fn set_name(widget: &impl IsA<gtk::Widget>, name: &str) {
+    if let Some(accessible) = widget.accessible() {
+        if accessible.name().as_deref() != Some(name) {
+            accessible.set_name(name);
+        }
+    }
+}
+
+pub fn name(widget: &impl IsA<gtk::Widget>) -> Option<String> {
+    Some(widget.accessible()?.name()?.to_string())
+}
+
+// what atk_object_announce does (atk >= 2.46), by signal name so older atk
+// without it just says nothing
+pub fn announce(widget: &impl IsA<gtk::Widget>, message: &str) {
+    if let Some(accessible) = widget.accessible() {
+        if let Err(err) = accessible.emit_by_name("announcement", &[&message]) {
+            log::debug!("can't announce {:?}: {}", message, err);
+        }
+    }
+}
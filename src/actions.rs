@@ -0,0 +1,369 @@
+use gio::SimpleAction;
+use gtk::prelude::*;
+use vte::TerminalExt;
+
+use crate::select::{self, Direction};
+use crate::{
+    connect, containers, history, keys, kubernetes, layout, lock, logging, marks, overview,
+    performance, platform, print, procs, redact, screenshot, search, secrets, snippets, theme,
+    tmux, watch, Term, FONT_SCALE_STEP,
+};
+
+macro_rules! actions {
+    ($($variant:ident => $name:literal, $label:literal, [$($accel:literal),*];)*) => {
+        #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+        pub enum Action {
+            $($variant,)*
+        }
+
+        impl Action {
+            pub const ALL: &'static [Action] = &[$(Action::$variant,)*];
+
+            pub fn name(self) -> &'static str {
+                match self {
+                    $(Action::$variant => $name,)*
+                }
+            }
+
+            pub fn label(self) -> &'static str {
+                match self {
+                    $(Action::$variant => $label,)*
+                }
+            }
+
+            pub fn default_accels(self) -> &'static [&'static str] {
+                match self {
+                    $(Action::$variant => &[$($accel),*],)*
+                }
+            }
+        }
+    };
+}
+
+actions! {
+    NewTab => "new_tab", "New Tab", ["<Alt>t"];
+    NewTabWithCommand => "new_tab_with_command", "New Tab with Command…", [];
+    CloseTab => "close_tab", "Close Tab", ["<Ctrl><Shift>w"];
+    SelectAll => "select_all", "Select All", ["<Ctrl><Shift>a"];
+    UnselectAll => "unselect_all", "Select None", [];
+    SearchSelection => "search_selection", "Search the Web", [];
+    OpenScrollbackInEditor => "open_scrollback_in_editor", "Open Scrollback in Editor", ["<Ctrl><Shift>e"];
+    TabOverview => "tab_overview", "Tab Overview", ["<Ctrl><Shift>o"];
+    ReloadConfig => "reload_config", "Reload Configuration", ["<Ctrl><Shift>r"];
+    ZoomIn => "zoom_in", "Zoom In", ["<Ctrl>plus", "<Ctrl>equal"];
+    ZoomOut => "zoom_out", "Zoom Out", ["<Ctrl>minus"];
+    ZoomReset => "zoom_reset", "Reset Zoom", ["<Ctrl>0"];
+    Reconnect => "reconnect", "Reconnect", [];
+    PinTab => "pin_tab", "Pin Tab", [];
+    CloseOtherTabs => "close_other_tabs", "Close Other Tabs", [];
+    CloseProjectTabs => "close_project_tabs", "Close Tabs in This Project", [];
+    SplitRight => "split_right", "Split Right", ["<Ctrl><Shift>d"];
+    SplitDown => "split_down", "Split Down", ["<Ctrl><Shift>s"];
+    ClosePane => "close_pane", "Close Pane", [];
+    FocusNextPane => "focus_next_pane", "Next Pane", ["<Ctrl><Shift>n"];
+    ToggleSyncScroll => "toggle_sync_scroll", "Synchronize Scrolling", [];
+    TogglePaneZoom => "toggle_pane_zoom", "Zoom Pane", ["<Ctrl><Shift>z"];
+    // no defaults, editors and readline select words with <Ctrl><Shift> arrows
+    ResizePaneLeft => "resize_pane_left", "Move Divider Left", [];
+    ResizePaneRight => "resize_pane_right", "Move Divider Right", [];
+    ResizePaneUp => "resize_pane_up", "Move Divider Up", [];
+    ResizePaneDown => "resize_pane_down", "Move Divider Down", [];
+    SaveLayoutAs => "save_layout_as", "Save Layout…", [];
+    OpenLayout => "open_layout", "Load Layout…", [];
+    AddWatch => "add_watch", "Add Watch…", [];
+    ClearWatches => "clear_watches", "Clear Watches", [];
+    ToggleTimestamps => "toggle_timestamps", "Show Timestamps", [];
+    ToggleFrameTiming => "toggle_frame_timing", "Show Frame Timing", [];
+    ScrollToMarker => "scroll_to_marker", "Scroll to New Output", ["<Ctrl><Shift>m"];
+    AddMark => "add_mark", "Add Mark…", ["<Ctrl><Shift>b"];
+    NextMark => "next_mark", "Next Mark", ["<Ctrl><Shift>Page_Down"];
+    PrevMark => "prev_mark", "Previous Mark", ["<Ctrl><Shift>Page_Up"];
+    Find => "find", "Find…", ["<Ctrl><Shift>f"];
+    FindNext => "find_next", "Find Next", ["<Ctrl><Shift>g"];
+    FindPrevious => "find_previous", "Find Previous", ["<Ctrl><Shift>h"];
+    FindSelection => "find_selection", "Find Selection", ["<Ctrl><Alt>f"];
+    PreviewThemes => "preview_themes", "Preview Themes…", [];
+    ToggleBigCursor => "toggle_big_cursor", "Big Cursor", [];
+    ToggleRedaction => "toggle_redaction", "Redact Secrets", [];
+    Screenshot => "screenshot", "Screenshot", ["<Ctrl><Shift>Print"];
+    Print => "print", "Print…", [];
+    // no default, <Shift>Up belongs to the programs in the terminal
+    SelectPreviousLine => "select_previous_line", "Select Previous Line", [];
+    ExtendSelectionUp => "extend_selection_up", "Extend Selection Up", [];
+    ExtendSelectionDown => "extend_selection_down", "Extend Selection Down", [];
+    ExtendSelectionLeft => "extend_selection_left", "Extend Selection Left", [];
+    ExtendSelectionRight => "extend_selection_right", "Extend Selection Right", [];
+    FindNextSelected => "find_next_selected", "Find Next Selected", [];
+    InsertUnicode => "insert_unicode", "Insert Character…", ["<Ctrl><Shift>i"];
+    OpenConfig => "open_config", "Preferences", ["<Ctrl>comma"];
+    ShowShortcuts => "show_shortcuts", "Keyboard Shortcuts", [];
+    About => "about", "About pterm", [];
+    RestartShell => "restart_shell", "Restart Shell", [];
+    ShowProcesses => "show_processes", "Processes", [];
+    LockTab => "lock_tab", "Lock Tab", ["<Ctrl><Shift>l"];
+    ShowDebugLog => "show_debug_log", "Debug Log", ["<Ctrl><Shift><Alt>d"];
+    GlobalHistory => "global_history", "Command History…", ["<Ctrl><Alt>r"];
+    InsertSnippet => "insert_snippet", "Insert Snippet…", ["<Ctrl><Shift>p"];
+    AutotypeSecret => "autotype_secret", "Type Secret…", [];
+    Connect => "connect", "Connect…", [];
+    NewTabInContainer => "new_tab_in_container", "New Tab in Container…", [];
+    KubernetesShell => "kubernetes_shell", "Kubernetes Shell…", [];
+    TmuxAttach => "tmux_attach", "Attach tmux Session…", [];
+    TmuxDetach => "tmux_detach", "Detach tmux", [];
+}
+
+impl Action {
+    // name as used by menus and accelerators
+    pub fn detailed_name(self) -> String {
+        format!("win.{}", self.name())
+    }
+}
+
+pub fn run(win: &Term, action: Action) {
+    match action {
+        Action::NewTab => win.add_new_tab(),
+        Action::NewTabWithCommand => win.prompt_command(),
+        Action::CloseTab => win.close_active_tab(),
+        Action::SelectAll => {
+            if let Some(terminal) = win.active_terminal() {
+                terminal.select_all();
+            }
+        }
+        Action::UnselectAll => {
+            if let Some(terminal) = win.active_terminal() {
+                terminal.unselect_all();
+            }
+        }
+        Action::SearchSelection => win.search_selection(),
+        Action::OpenScrollbackInEditor => {
+            if let Err(err) = win.open_scrollback_in_editor() {
+                log::error!("failed to open scrollback: {}", err);
+            }
+        }
+        Action::TabOverview => overview::show(win),
+        Action::ReloadConfig => win.reload_config(),
+        Action::ZoomIn => win.zoom(Some(FONT_SCALE_STEP)),
+        Action::ZoomOut => win.zoom(Some(1.0 / FONT_SCALE_STEP)),
+        Action::ZoomReset => win.zoom(None),
+        Action::Reconnect => win.reconnect(),
+        Action::PinTab => win.toggle_pin(),
+        Action::CloseOtherTabs => win.close_other_tabs(),
+        Action::CloseProjectTabs => win.close_project_tabs(),
+        Action::SplitRight => win.split(gtk::Orientation::Horizontal),
+        Action::SplitDown => win.split(gtk::Orientation::Vertical),
+        Action::ClosePane => {
+            if let Some(terminal) = win.active_terminal() {
+                win.close_pane(&terminal);
+            }
+        }
+        Action::FocusNextPane => win.focus_next_pane(),
+        Action::ToggleSyncScroll => win.toggle_sync_scroll(),
+        Action::TogglePaneZoom => win.toggle_pane_zoom(),
+        Action::ResizePaneLeft => win.resize_pane(gtk::Orientation::Horizontal, -1),
+        Action::ResizePaneRight => win.resize_pane(gtk::Orientation::Horizontal, 1),
+        Action::ResizePaneUp => win.resize_pane(gtk::Orientation::Vertical, -1),
+        Action::ResizePaneDown => win.resize_pane(gtk::Orientation::Vertical, 1),
+        Action::SaveLayoutAs => layout::prompt(win, "Save Layout", "save_layout"),
+        Action::OpenLayout => layout::prompt(win, "Load Layout", "load_layout"),
+        Action::AddWatch => watch::prompt(win),
+        Action::ClearWatches => win.clear_watches(),
+        Action::ToggleTimestamps => win.toggle_timestamps(),
+        Action::ToggleFrameTiming => performance::toggle_overlay(win),
+        Action::AddMark => marks::prompt_bookmark(win),
+        Action::NextMark | Action::PrevMark => {
+            if let Some(terminal) = win.active_terminal() {
+                marks::jump_to_bookmark(win, &terminal, action == Action::NextMark);
+            }
+        }
+        Action::Find => search::show(win),
+        Action::FindNext => search::find(win, Some(true)),
+        Action::FindPrevious => search::find(win, Some(false)),
+        Action::FindSelection => search::find_selection(win),
+        Action::PreviewThemes => theme::preview(win),
+        Action::ToggleBigCursor => win.toggle_big_cursor(),
+        Action::ToggleRedaction => redact::toggle(win),
+        Action::Screenshot => screenshot::screenshot(win),
+        Action::Print => print::print(win),
+        Action::SelectPreviousLine => {
+            if let Some(terminal) = win.active_terminal() {
+                select::select_previous_line(win, &terminal);
+            }
+        }
+        Action::ExtendSelectionUp
+        | Action::ExtendSelectionDown
+        | Action::ExtendSelectionLeft
+        | Action::ExtendSelectionRight => {
+            let direction = match action {
+                Action::ExtendSelectionUp => Direction::Up,
+                Action::ExtendSelectionDown => Direction::Down,
+                Action::ExtendSelectionLeft => Direction::Left,
+                _ => Direction::Right,
+            };
+            if let Some(terminal) = win.active_terminal() {
+                select::extend(win, &terminal, direction);
+            }
+        }
+        Action::FindNextSelected => select::find_next_selected(win),
+        Action::InsertUnicode => win.insert_unicode(),
+        Action::OpenConfig => win.open_config(),
+        Action::ShowShortcuts => show_shortcuts(win),
+        Action::About => win.show_about(),
+        Action::RestartShell => win.restart_shell(),
+        Action::ShowProcesses => procs::show(win),
+        Action::LockTab => lock::toggle(win),
+        Action::ShowDebugLog => logging::show_window(win),
+        Action::GlobalHistory => history::show(win),
+        Action::InsertSnippet => snippets::show(win),
+        Action::AutotypeSecret => secrets::autotype(win),
+        Action::Connect => connect::show(win),
+        Action::NewTabInContainer => containers::show(win),
+        Action::KubernetesShell => kubernetes::show(win),
+        Action::TmuxAttach => tmux::attach(win),
+        Action::TmuxDetach => tmux::detach(win),
+        Action::ScrollToMarker => {
+            if let Some(terminal) = win.active_terminal() {
+                marks::scroll_to_marker(win, &terminal);
+            }
+        }
+    }
+}
+
+fn signal_foreground(win: &Term, signal: platform::Signal) {
+    let pgrp = win
+        .active_terminal()
+        .and_then(|terminal| terminal.pty())
+        .and_then(|pty| platform::foreground_pid(pty.fd()));
+    let pgrp = match pgrp {
+        Some(pgrp) => pgrp,
+        None => return,
+    };
+    log::info!("sending {:?} to process group {}", signal, pgrp);
+    if let Err(err) = platform::signal_group(pgrp, signal) {
+        win.show_warning(&format!("can't signal process group {}: {}", pgrp, err));
+    }
+}
+
+// every action with whatever accelerators it has now
+fn show_shortcuts(win: &Term) {
+    let app = win.application().unwrap();
+    let dialog = gtk::Dialog::with_buttons(
+        Some("Keyboard Shortcuts"),
+        Some(win),
+        gtk::DialogFlags::DESTROY_WITH_PARENT,
+        &[("Close", gtk::ResponseType::Close)],
+    );
+    let grid = gtk::Grid::builder()
+        .column_spacing(24)
+        .row_spacing(4)
+        .margin(12)
+        .build();
+    for (row, &action) in Action::ALL.iter().enumerate() {
+        let accels: Vec<String> = app
+            .accels_for_action(&action.detailed_name())
+            .iter()
+            .map(|accel| {
+                let (key, modifiers) = gtk::accelerator_parse(accel);
+                gtk::accelerator_get_label(key, modifiers).to_string()
+            })
+            .collect();
+        let name = gtk::Label::builder()
+            .label(action.label())
+            .xalign(0.0)
+            .build();
+        let keys = gtk::Label::builder()
+            .label(&accels.join(", "))
+            .xalign(0.0)
+            .build();
+        keys.style_context().add_class("dim-label");
+        grid.attach(&name, 0, row as i32, 1, 1);
+        grid.attach(&keys, 1, row as i32, 1, 1);
+    }
+    let scrolled = gtk::ScrolledWindow::builder()
+        .hscrollbar_policy(gtk::PolicyType::Never)
+        .min_content_height(400)
+        .child(&grid)
+        .build();
+    dialog.content_area().pack_start(&scrolled, true, true, 0);
+    dialog.connect_response(|dialog, _| dialog.close());
+    dialog.show_all();
+}
+
+// use_physical_keys: runs the action whose accelerator is on the key in the
+// same place on a US keyboard, if that's not the key pressed
+pub fn activate_by_position(win: &Term, event: &gdk::EventKey) -> bool {
+    let modifiers = event.state() & gtk::accelerator_get_default_mod_mask();
+    if !modifiers.intersects(gdk::ModifierType::CONTROL_MASK | gdk::ModifierType::MOD1_MASK) {
+        return false;
+    }
+    let key = match keys::position(event) {
+        Some(key) if key != event.keyval().to_lower() => key,
+        _ => return false,
+    };
+    let app = match win.application() {
+        Some(app) => app,
+        None => return false,
+    };
+    let bound = Action::ALL.iter().copied().find(|action| {
+        app.accels_for_action(&action.detailed_name())
+            .iter()
+            .filter_map(|accel| keys::parse_key(accel))
+            .any(|(accel, accel_modifiers)| accel == key && accel_modifiers == modifiers)
+    });
+    match bound {
+        Some(action) => {
+            run(win, action);
+            true
+        }
+        None => false,
+    }
+}
+
+// installs every action on the window as "win.<name>" with its default accels
+pub fn register(win: &Term) {
+    let app = win.application().unwrap();
+
+    for &action in Action::ALL {
+        let simple = SimpleAction::new(action.name(), None);
+        app.set_accels_for_action(&action.detailed_name(), action.default_accels());
+        simple.connect_activate(glib::clone!(@weak win => move |_, _| {
+            run(&win, action);
+        }));
+        win.add_action(&simple);
+    }
+
+    // tabs are numbered from 1, like in the labels
+    let switch_tab = SimpleAction::new("switch_tab", Some(glib::VariantTy::UINT32));
+    switch_tab.connect_activate(glib::clone!(@weak win => move |_, param| {
+        if let Some(page) = param.and_then(|param| param.get::<u32>()) {
+            win.notebook().set_current_page(page.checked_sub(1));
+        }
+    }));
+    win.add_action(&switch_tab);
+
+    // "win.signal_foreground::int", see platform::Signal for the names
+    let signal = SimpleAction::new("signal_foreground", Some(glib::VariantTy::STRING));
+    signal.connect_activate(glib::clone!(@weak win => move |_, param| {
+        let signal = param
+            .and_then(|param| param.get::<String>())
+            .and_then(|name| platform::Signal::from_name(&name));
+        if let Some(signal) = signal {
+            signal_foreground(&win, signal);
+        }
+    }));
+    win.add_action(&signal);
+
+    // the layout name comes as the parameter, e.g. "win.load_layout::work"
+    let layout_actions: [(&str, fn(&Term, &str) -> Result<(), anyhow::Error>); 2] =
+        [("save_layout", layout::save), ("load_layout", layout::load)];
+    for (name, run) in layout_actions {
+        let action = SimpleAction::new(name, Some(glib::VariantTy::STRING));
+        action.connect_activate(glib::clone!(@weak win => move |_, param| {
+            if let Some(layout) = param.and_then(|param| param.get::<String>()) {
+                if let Err(err) = run(&win, &layout) {
+                    win.show_warning(&format!("{} {:?}: {}", name, layout, err));
+                }
+            }
+        }));
+        win.add_action(&action);
+    }
+}
@@ -0,0 +1,84 @@
+// `pterm --benchmark`: push a lot of output through a tab and report how fast
+// vte got through it, to catch regressions in the rendering path.
+
+use std::cell::RefCell;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::time::Instant;
+
+use anyhow::Error;
+use gtk::prelude::*;
+use vte::TerminalExt;
+
+const YES_BYTES: u64 = 64 * 1024 * 1024;
+
+#[derive(Debug)]
+pub enum Source {
+    Yes,
+    File(PathBuf),
+}
+
+impl Source {
+    // command to run and the number of bytes it will print
+    pub fn command(&self) -> Result<(Vec<String>, u64), Error> {
+        match self {
+            Source::Yes => Ok((
+                vec![
+                    "sh".to_string(),
+                    "-c".to_string(),
+                    format!("yes | head -c {}", YES_BYTES),
+                ],
+                YES_BYTES,
+            )),
+            Source::File(path) => {
+                let len = std::fs::metadata(path)?.len();
+                Ok((vec!["cat".to_string(), path.display().to_string()], len))
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct Frames {
+    count: u64,
+    // frame clock times, in microseconds
+    last: Option<i64>,
+    longest: i64,
+}
+
+pub fn attach(terminal: &vte::Terminal, bytes: u64) {
+    let start = Instant::now();
+    let frames = Rc::new(RefCell::new(Frames::default()));
+
+    terminal.add_tick_callback(glib::clone!(@strong frames => move |_, clock| {
+        let now = clock.frame_time();
+        let mut frames = frames.borrow_mut();
+        if let Some(last) = frames.last {
+            frames.longest = frames.longest.max(now - last);
+        }
+        frames.last = Some(now);
+        frames.count += 1;
+        glib::Continue(true)
+    }));
+
+    terminal.connect_child_exited(move |_, status| {
+        let elapsed = start.elapsed().as_secs_f64();
+        let frames = frames.borrow();
+        println!(
+            "{} bytes in {:.2}s: {:.1} MiB/s (exit status {})",
+            bytes,
+            elapsed,
+            bytes as f64 / elapsed / (1024.0 * 1024.0),
+            status
+        );
+        println!(
+            "{} frames, {:.1} fps, longest frame {:.1} ms",
+            frames.count,
+            frames.count as f64 / elapsed,
+            frames.longest as f64 / 1000.0
+        );
+        if let Some(app) = gio::Application::default() {
+            app.quit();
+        }
+    });
+}
@@ -0,0 +1,106 @@
+use std::path::PathBuf;
+
+use anyhow::{bail, Error};
+
+use crate::{bench, serial, theme};
+
+const CONFIG_ENV: &str = "PTERM_CONFIG";
+
+#[derive(Debug, Default)]
+pub struct Args {
+    pub config: Option<PathBuf>,
+    pub benchmark: Option<bench::Source>,
+    pub import_theme: Option<theme::Import>,
+    // ctl: the request for the running pterm, unix only
+    pub ctl: Option<Vec<String>>,
+    // ssh: a [[connections]] name, or a destination and ssh's arguments
+    pub ssh: Option<Vec<String>>,
+    // serial: opened as a tab running the bridge
+    pub serial: Option<serial::Port>,
+    // the bridge itself, running in that tab
+    pub serial_bridge: Option<serial::Port>,
+    // reopen the sessions of detachable windows that were closed
+    pub attach: bool,
+    // -e: run this instead of the shell
+    pub command: Option<Vec<String>>,
+    pub version: bool,
+    // whatever we don't understand is left to gtk
+    pub rest: Vec<String>,
+}
+
+impl Args {
+    pub fn parse() -> Result<Args, Error> {
+        let mut parsed = Args::default();
+        let mut args = std::env::args();
+
+        parsed.rest.extend(args.next());
+        while let Some(arg) = args.next() {
+            if arg == "--config" || arg == "-c" {
+                match args.next() {
+                    Some(path) => parsed.config = Some(PathBuf::from(path)),
+                    None => bail!("{} requires a path", arg),
+                }
+            } else if let Some(path) = arg.strip_prefix("--config=") {
+                parsed.config = Some(PathBuf::from(path));
+            } else if arg == "import-theme" && parsed.rest.len() == 1 {
+                parsed.import_theme = Some(parse_import(&mut args)?);
+            } else if arg == "ctl" && parsed.rest.len() == 1 {
+                parsed.ctl = Some(args.by_ref().collect());
+            } else if arg == "ssh" && parsed.rest.len() == 1 {
+                let ssh: Vec<String> = args.by_ref().collect();
+                if ssh.is_empty() {
+                    bail!("usage: pterm ssh <name | [user@]host> [ssh arguments]");
+                }
+                parsed.ssh = Some(ssh);
+            } else if arg == "serial" && parsed.rest.len() == 1 {
+                parsed.serial = Some(serial::parse(&mut args)?);
+            } else if arg == serial::BRIDGE_FLAG && parsed.rest.len() == 1 {
+                parsed.serial_bridge = Some(serial::parse(&mut args)?);
+            } else if arg == "attach" && parsed.rest.len() == 1 {
+                parsed.attach = true;
+            } else if arg == "-e" || arg == "--command" {
+                let command: Vec<String> = args.by_ref().collect();
+                if command.is_empty() {
+                    bail!("{} requires a command", arg);
+                }
+                parsed.command = Some(command);
+            } else if arg == "--version" || arg == "-V" {
+                parsed.version = true;
+            } else if arg == "--benchmark" {
+                parsed.benchmark = Some(bench::Source::Yes);
+            } else if let Some(path) = arg.strip_prefix("--benchmark=") {
+                parsed.benchmark = Some(bench::Source::File(PathBuf::from(path)));
+            } else {
+                parsed.rest.push(arg);
+            }
+        }
+        if parsed.config.is_none() {
+            parsed.config = std::env::var_os(CONFIG_ENV).map(PathBuf::from);
+        }
+        Ok(parsed)
+    }
+}
+
+// import-theme <file> [--install <name>]
+fn parse_import(args: &mut impl Iterator<Item = String>) -> Result<theme::Import, Error> {
+    let mut file = None;
+    let mut install = None;
+    while let Some(arg) = args.next() {
+        if arg == "--install" {
+            match args.next() {
+                Some(name) => install = Some(name),
+                None => bail!("--install requires a theme name"),
+            }
+        } else if let Some(name) = arg.strip_prefix("--install=") {
+            install = Some(name.to_string());
+        } else if file.is_none() {
+            file = Some(PathBuf::from(arg));
+        } else {
+            bail!("unexpected argument {:?} for import-theme", arg);
+        }
+    }
+    match file {
+        Some(file) => Ok(theme::Import { file, install }),
+        None => bail!("usage: pterm import-theme <file> [--install <name>]"),
+    }
+}
@@ -0,0 +1,1218 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::Error;
+use pango::FontDescription;
+use serde::{Deserialize, Serialize};
+
+use regex::Regex;
+
+use crate::keys::{self, KeyBinding};
+use crate::watch::{self, TriggerAction};
+use crate::{hacks, kubernetes, links, marks, redact, snippets, ssh, tabs, theme, titles};
+
+pub const DEFAULT_CONFIG: &str = include_str!("../config.toml");
+
+// in place of vte's palette, which is made for dark backgrounds, for the
+// colors a light [colors] doesn't set
+const LIGHT_PALETTE: [&str; 16] = [
+    "#000000", "#c01c28", "#26a269", "#a2734c", "#12488b", "#a347ba", "#2aa1b3", "#5e5c64",
+    "#3d3846", "#e01b24", "#2ec27e", "#c64600", "#1c71d8", "#c061cb", "#1a8fa0", "#77767b",
+];
+// below these contrast ratios with the background the config gets a warning
+const MIN_FOREGROUND_CONTRAST: f64 = 3.0;
+const MIN_PALETTE_CONTRAST: f64 = 1.5;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ColorConfig {
+    pub foreground: String,
+    pub background: String,
+    pub palette: Vec<String>,
+    // block color and the text under it, vte's reverse video when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cursor_foreground: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Config {
+    pub font_family: FontFamilies,
+    pub font_size: u32,
+    #[serde(default = "default_search_url")]
+    pub search_url: String,
+    #[serde(default = "default_title_template")]
+    pub title_template: String,
+    // how {cwd} is written in titles
+    #[serde(default)]
+    pub title_path: TitlePath,
+    // drawn large in the top right corner of the terminal, empty for none
+    #[serde(default)]
+    pub badge_template: String,
+    #[serde(default = "default_true")]
+    pub per_tab_zoom: bool,
+    // seconds
+    #[serde(default)]
+    pub spawn_timeout: Option<u64>,
+    #[serde(default)]
+    pub close_on_exit: CloseOnExit,
+    // seconds to keep the exit status on screen before closing
+    #[serde(default)]
+    pub close_delay: u64,
+    // scroll by pixels instead of whole rows, smoother with touchpads
+    #[serde(default)]
+    pub scroll_unit_is_pixels: bool,
+    // -1 for no limit, vte's default if unset
+    #[serde(default)]
+    pub scrollback_lines: Option<i64>,
+    #[serde(default)]
+    pub scrollback_storage: ScrollbackStorage,
+    #[serde(default)]
+    pub new_tab_position: NewTabPosition,
+    #[serde(default = "default_true")]
+    pub focus_new_tab: bool,
+    #[serde(default)]
+    pub inherit_env: Vec<String>,
+    #[serde(default = "default_ssh_title_template")]
+    pub ssh_title_template: String,
+    // {title} is the repository and branch of the cwd, when it's in one
+    #[serde(default)]
+    pub git_title: bool,
+    // percent, 0 disables
+    #[serde(default)]
+    pub dim_inactive: u8,
+    // cells
+    #[serde(default = "default_pane_resize_step")]
+    pub pane_resize_step: u32,
+    // seconds, needs shell integration
+    #[serde(default = "default_long_command")]
+    pub long_command: u64,
+    // has to exit successfully to unlock a locked tab, e.g. ["pkexec", "true"]
+    #[serde(default)]
+    pub unlock_command: Vec<String>,
+    // seconds without typing after which a tab that was locked locks again
+    #[serde(default)]
+    pub lock_idle: Option<u64>,
+    // password manager for autotype_secret, off when unset
+    #[serde(default)]
+    pub secret_backend: Option<SecretBackend>,
+    // percent of a core, off when unset
+    #[serde(default)]
+    pub busy_cpu: Option<u32>,
+    // seconds, off when unset
+    #[serde(default)]
+    pub hibernate_after: Option<u64>,
+    // on coming back to the window, go to the one tab that printed meanwhile
+    #[serde(default)]
+    pub focus_follows_output: bool,
+    #[serde(default)]
+    pub link_activation: LinkActivation,
+    // arabic and other scripts that join letters, not ligatures
+    #[serde(default = "default_true")]
+    pub text_shaping: bool,
+    #[serde(default = "default_true")]
+    pub bidi: bool,
+    #[serde(default)]
+    pub text_blink_mode: TextBlinkMode,
+    #[serde(default)]
+    pub cursor_blink: CursorBlink,
+    // seconds of no typing after which the cursor stops blinking
+    #[serde(default)]
+    pub cursor_blink_timeout: Option<u32>,
+    // no flashing tabs or blinking text
+    #[serde(default)]
+    pub reduce_motion: ReduceMotion,
+    // middle click on the empty part of the tab bar runs the selection in a new tab
+    #[serde(default)]
+    pub middle_click_command: bool,
+    // gtk input method module, "none" or "simple" to go without ibus and co
+    #[serde(default)]
+    pub input_method: Option<String>,
+    // false: Alt+key types what AltGr+key would, or the key itself
+    #[serde(default = "default_true")]
+    pub alt_sends_escape: bool,
+    // shortcuts and [[keys]] by where the key is rather than what it types
+    #[serde(default)]
+    pub use_physical_keys: bool,
+    // Ctrl+C copies when something is selected, Ctrl+V pastes
+    #[serde(default)]
+    pub ctrl_c_copies: bool,
+    // whatever the program running asks for
+    #[serde(default)]
+    pub application_cursor: ApplicationMode,
+    #[serde(default)]
+    pub application_keypad: ApplicationMode,
+    // outline around the cursor of the focused terminal, for low vision
+    #[serde(default)]
+    pub big_cursor: bool,
+    // and a band across its row
+    #[serde(default)]
+    pub cursor_crosshair: bool,
+    // start with toggle_redaction on
+    #[serde(default)]
+    pub redacted: bool,
+    #[serde(default)]
+    pub screenshot_area: ScreenshotArea,
+    // print in the terminal colors instead of black on white
+    #[serde(default)]
+    pub print_colors: bool,
+    // tabs run in pterm's own tmux server and outlive the window
+    #[serde(default)]
+    pub detachable: bool,
+    // contrast ratio between text and background, 1 to 21
+    #[serde(default = "default_minimum_contrast")]
+    pub minimum_contrast: f64,
+    // name of a theme installed with import-theme, replaces [colors]
+    #[serde(default)]
+    pub theme: Option<String>,
+    pub colors: ColorConfig,
+    #[serde(default)]
+    pub triggers: Vec<TriggerConfig>,
+    #[serde(default)]
+    pub highlights: Vec<HighlightConfig>,
+    // more to cover while redacting, regexes
+    #[serde(default)]
+    pub redact: Vec<String>,
+    #[serde(default)]
+    pub keys: Vec<KeyConfig>,
+    #[serde(default)]
+    pub hosts: Vec<HostConfig>,
+    #[serde(default)]
+    pub connections: Vec<ConnectionConfig>,
+    #[serde(default)]
+    pub title_rules: Vec<TitleRuleConfig>,
+    // "Kubernetes Shell…" is there with this section
+    #[serde(default)]
+    pub kubernetes: Option<KubernetesConfig>,
+    #[serde(default)]
+    pub performance: PerformanceConfig,
+    #[serde(default)]
+    pub matches: Vec<MatchConfig>,
+    #[serde(default)]
+    pub clicks: Vec<ClickConfig>,
+    // name to text
+    #[serde(default)]
+    pub snippets: BTreeMap<String, String>,
+}
+
+// another kind of text to click on besides url, file and email
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MatchConfig {
+    pub name: String,
+    pub regex: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClickConfig {
+    // url, file, email or the name of one of [[matches]]
+    #[serde(rename = "match")]
+    pub class: String,
+    pub gesture: Gesture,
+    pub action: ClickAction,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Gesture {
+    Click,
+    CtrlClick,
+    DoubleClick,
+    MiddleClick,
+    RightClick,
+}
+
+// how a url is opened in the browser
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LinkActivation {
+    Click,
+    CtrlClick,
+    Never,
+}
+
+impl Default for LinkActivation {
+    fn default() -> Self {
+        LinkActivation::CtrlClick
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ClickAction {
+    Open,
+    Copy,
+    // typed into the terminal
+    Paste,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HostConfig {
+    pub pattern: String,
+    #[serde(default)]
+    pub background: Option<String>,
+    #[serde(default)]
+    pub tint: Option<String>,
+}
+
+// regexes, all of those given have to match
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TitleRuleConfig {
+    #[serde(default)]
+    pub command: Option<String>,
+    #[serde(default)]
+    pub cwd: Option<String>,
+    #[serde(default)]
+    pub host: Option<String>,
+    pub title: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ConnectionConfig {
+    pub name: String,
+    // [user@]host
+    pub host: String,
+    #[serde(default)]
+    pub port: Option<u16>,
+    #[serde(default)]
+    pub mosh: bool,
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub command: Option<String>,
+    #[serde(default)]
+    pub background: Option<String>,
+    #[serde(default)]
+    pub tint: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct KubernetesConfig {
+    #[serde(default = "default_kubectl")]
+    pub kubectl: String,
+    // what's run in the pod
+    #[serde(default = "default_pod_shell")]
+    pub shell: String,
+    // the contexts to pick from, all of kubectl's when empty
+    #[serde(default)]
+    pub contexts: Vec<String>,
+    #[serde(default)]
+    pub background: Option<String>,
+    #[serde(default = "default_kubernetes_tint")]
+    pub tint: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PerformanceConfig {
+    // times what vte scrolls by a wheel notch
+    #[serde(default = "default_scroll_speed")]
+    pub scroll_speed: f64,
+    // false paints output as soon as it's in rather than at the next frame
+    #[serde(default = "default_true")]
+    pub sync_to_vblank: bool,
+    // highlights, triggers and timestamps wait until vte has painted
+    #[serde(default)]
+    pub low_latency: bool,
+}
+
+impl Default for PerformanceConfig {
+    fn default() -> Self {
+        PerformanceConfig {
+            scroll_speed: default_scroll_speed(),
+            sync_to_vblank: true,
+            low_latency: false,
+        }
+    }
+}
+
+fn default_scroll_speed() -> f64 {
+    1.0
+}
+
+fn default_kubectl() -> String {
+    "kubectl".to_string()
+}
+
+fn default_pod_shell() -> String {
+    "sh".to_string()
+}
+
+fn default_kubernetes_tint() -> Option<String> {
+    Some("rgba(50, 108, 229, 0.2)".to_string())
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct KeyConfig {
+    pub key: String,
+    #[serde(flatten)]
+    pub action: KeyActionConfig,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyActionConfig {
+    // sent as it is
+    SendText(String),
+    // with \e, \xNN and friends turned into bytes
+    SendEscape(String),
+}
+
+// one family or a list, later ones are used for characters the first lacks
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum FontFamilies {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl FontFamilies {
+    // pango takes fallbacks as a comma separated family
+    fn pango_family(&self) -> String {
+        match self {
+            FontFamilies::One(family) => family.clone(),
+            FontFamilies::Many(families) => families.join(","),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HighlightConfig {
+    pub regex: String,
+    #[serde(default = "default_highlight_color")]
+    pub color: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TriggerConfig {
+    pub regex: String,
+    #[serde(flatten)]
+    pub action: TriggerActionConfig,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum TriggerActionConfig {
+    Highlight {
+        #[serde(default = "default_highlight_color")]
+        color: String,
+    },
+    Sound,
+    Copy {
+        #[serde(default)]
+        group: usize,
+    },
+    Run {
+        command: Vec<String>,
+    },
+    Notify,
+    Progress {
+        #[serde(default = "default_progress_group")]
+        group: usize,
+    },
+}
+
+fn default_progress_group() -> usize {
+    1
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CloseOnExit {
+    Always,
+    OnSuccess,
+    Never,
+}
+
+impl Default for CloseOnExit {
+    fn default() -> Self {
+        CloseOnExit::Always
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TextBlinkMode {
+    Never,
+    Focused,
+    Always,
+}
+
+impl Default for TextBlinkMode {
+    fn default() -> Self {
+        TextBlinkMode::Always
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CursorBlink {
+    // whatever the desktop settings say
+    System,
+    On,
+    Off,
+}
+
+impl Default for CursorBlink {
+    fn default() -> Self {
+        CursorBlink::System
+    }
+}
+
+// where vte's scrollback files go, see scrollback.rs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScrollbackStorage {
+    // the temporary directory, a tmpfs on many systems
+    Memory,
+    Disk,
+}
+
+impl Default for ScrollbackStorage {
+    fn default() -> Self {
+        ScrollbackStorage::Memory
+    }
+}
+
+// application cursor keys (DECCKM) and keypad (DECKPAM)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ApplicationMode {
+    // as the program asks
+    Auto,
+    On,
+    Off,
+}
+
+impl Default for ApplicationMode {
+    fn default() -> Self {
+        ApplicationMode::Auto
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SecretBackend {
+    SecretService,
+    Pass,
+    Rbw,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScreenshotArea {
+    // the focused pane
+    Terminal,
+    // all panes of the tab
+    Tab,
+    // the tabs with the tab bar
+    Window,
+}
+
+impl Default for ScreenshotArea {
+    fn default() -> Self {
+        ScreenshotArea::Terminal
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TitlePath {
+    Full,
+    // the home directory as ~
+    Home,
+    // ~ and only the first letter of every directory but the last
+    Short,
+}
+
+impl Default for TitlePath {
+    fn default() -> Self {
+        TitlePath::Home
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReduceMotion {
+    // on when the desktop turns animations off
+    System,
+    On,
+    Off,
+}
+
+impl Default for ReduceMotion {
+    fn default() -> Self {
+        ReduceMotion::System
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NewTabPosition {
+    End,
+    AfterCurrent,
+    // next to the tabs in the same git repository
+    Project,
+}
+
+impl Default for NewTabPosition {
+    fn default() -> Self {
+        NewTabPosition::End
+    }
+}
+
+fn default_search_url() -> String {
+    "https://duckduckgo.com/?q={query}".to_string()
+}
+
+fn default_title_template() -> String {
+    "{title} {zoom}".to_string()
+}
+
+fn default_ssh_title_template() -> String {
+    "{user}@{remote}".to_string()
+}
+
+fn default_pane_resize_step() -> u32 {
+    2
+}
+
+fn default_long_command() -> u64 {
+    10
+}
+
+fn default_minimum_contrast() -> f64 {
+    1.0
+}
+
+fn default_highlight_color() -> String {
+    "rgba(255, 255, 0, 0.25)".to_string()
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl Config {
+    pub fn from_file(path: &Path) -> Result<Config, Error> {
+        let file = std::fs::read(path)?;
+        let config = toml::from_slice(&file)?;
+        Ok(config)
+    }
+
+    pub fn from_str(s: &str) -> Result<Config, Error> {
+        let config = toml::from_str(s)?;
+        Ok(config)
+    }
+}
+
+pub struct TerminalConfig {
+    pub font: FontDescription,
+    pub foreground: gdk::RGBA,
+    pub background: gdk::RGBA,
+    pub palette: Vec<gdk::RGBA>,
+    pub cursor: Option<gdk::RGBA>,
+    pub cursor_foreground: Option<gdk::RGBA>,
+    pub search_url: String,
+    pub title_template: String,
+    pub title_path: TitlePath,
+    pub badge_template: String,
+    pub per_tab_zoom: bool,
+    pub spawn_timeout: Option<Duration>,
+    pub close_on_exit: CloseOnExit,
+    pub close_delay: Duration,
+    pub scroll_unit_is_pixels: bool,
+    pub scrollback_lines: Option<i64>,
+    pub scrollback_storage: ScrollbackStorage,
+    pub new_tab_position: NewTabPosition,
+    pub focus_new_tab: bool,
+    pub inherit_env: Vec<String>,
+    pub ssh_title_template: String,
+    pub git_title: bool,
+    pub dim_inactive: u8,
+    pub pane_resize_step: u32,
+    pub long_command: Duration,
+    pub unlock_command: Vec<String>,
+    pub lock_idle: Option<Duration>,
+    pub secret_backend: Option<SecretBackend>,
+    pub busy_cpu: Option<u32>,
+    pub hibernate_after: Option<Duration>,
+    pub focus_follows_output: bool,
+    pub text_shaping: bool,
+    pub bidi: bool,
+    pub text_blink_mode: TextBlinkMode,
+    pub cursor_blink: CursorBlink,
+    pub cursor_blink_timeout: Option<u32>,
+    pub reduce_motion: ReduceMotion,
+    pub middle_click_command: bool,
+    pub input_method: Option<String>,
+    pub alt_sends_escape: bool,
+    pub use_physical_keys: bool,
+    pub ctrl_c_copies: bool,
+    pub application_cursor: ApplicationMode,
+    pub application_keypad: ApplicationMode,
+    pub big_cursor: bool,
+    pub cursor_crosshair: bool,
+    pub minimum_contrast: f64,
+    pub triggers: Vec<watch::Trigger>,
+    pub highlights: Vec<marks::HighlightRule>,
+    pub redact: bool,
+    pub screenshot_area: ScreenshotArea,
+    pub print_colors: bool,
+    pub detachable: bool,
+    // built-in patterns, then redact
+    pub redactions: Vec<Regex>,
+    pub hosts: Vec<ssh::HostColors>,
+    pub connections: Vec<ssh::Connection>,
+    pub title_rules: Vec<titles::TitleRule>,
+    pub kubernetes: Option<kubernetes::Settings>,
+    pub scroll_speed: f64,
+    pub sync_to_vblank: bool,
+    pub low_latency: bool,
+    pub matches: Vec<links::Pattern>,
+    // [[clicks]] first, then the defaults
+    pub clicks: Vec<ClickConfig>,
+    pub keys: Vec<KeyBinding>,
+    // by name
+    pub snippets: Vec<(String, String)>,
+}
+
+impl Default for TerminalConfig {
+    fn default() -> Self {
+        TerminalConfig {
+            font: FontDescription::default(),
+            foreground: gdk::RGBA::white(),
+            background: gdk::RGBA::black(),
+            palette: Vec::new(),
+            cursor: None,
+            cursor_foreground: None,
+            search_url: default_search_url(),
+            title_template: default_title_template(),
+            title_path: TitlePath::default(),
+            badge_template: String::new(),
+            per_tab_zoom: true,
+            spawn_timeout: None,
+            close_on_exit: CloseOnExit::default(),
+            close_delay: Duration::ZERO,
+            scroll_unit_is_pixels: false,
+            scrollback_lines: None,
+            scrollback_storage: ScrollbackStorage::default(),
+            new_tab_position: NewTabPosition::default(),
+            focus_new_tab: true,
+            inherit_env: Vec::new(),
+            ssh_title_template: default_ssh_title_template(),
+            git_title: false,
+            dim_inactive: 0,
+            pane_resize_step: default_pane_resize_step(),
+            long_command: Duration::from_secs(default_long_command()),
+            unlock_command: Vec::new(),
+            secret_backend: None,
+            lock_idle: None,
+            busy_cpu: None,
+            hibernate_after: None,
+            focus_follows_output: false,
+            text_shaping: true,
+            bidi: true,
+            text_blink_mode: TextBlinkMode::default(),
+            cursor_blink: CursorBlink::default(),
+            cursor_blink_timeout: None,
+            reduce_motion: ReduceMotion::default(),
+            middle_click_command: false,
+            input_method: None,
+            alt_sends_escape: true,
+            use_physical_keys: false,
+            ctrl_c_copies: false,
+            application_cursor: ApplicationMode::default(),
+            application_keypad: ApplicationMode::default(),
+            big_cursor: false,
+            cursor_crosshair: false,
+            minimum_contrast: default_minimum_contrast(),
+            triggers: Vec::new(),
+            highlights: Vec::new(),
+            redact: false,
+            screenshot_area: ScreenshotArea::default(),
+            print_colors: false,
+            detachable: false,
+            redactions: redact::builtin(),
+            hosts: Vec::new(),
+            connections: Vec::new(),
+            title_rules: Vec::new(),
+            kubernetes: None,
+            scroll_speed: default_scroll_speed(),
+            sync_to_vblank: true,
+            low_latency: false,
+            matches: links::builtin(),
+            clicks: links::default_clicks(LinkActivation::default()),
+            keys: Vec::new(),
+            snippets: Vec::new(),
+        }
+    }
+}
+
+fn parse_color(color: &str, default: gdk::RGBA, errors: &mut Vec<String>) -> gdk::RGBA {
+    hacks::parse_color(color).unwrap_or_else(|_| {
+        errors.push(format!("invalid color {:?}", color));
+        default
+    })
+}
+
+fn parse_trigger(trigger: &TriggerConfig, errors: &mut Vec<String>) -> Option<watch::Trigger> {
+    let regex = match Regex::new(&trigger.regex) {
+        Ok(regex) => regex,
+        Err(err) => {
+            errors.push(format!(
+                "invalid trigger regex {:?}: {}",
+                trigger.regex, err
+            ));
+            return None;
+        }
+    };
+    let action = match &trigger.action {
+        TriggerActionConfig::Highlight { color } => TriggerAction::Highlight(parse_color(
+            color,
+            hacks::parse_color(&default_highlight_color()).unwrap(),
+            errors,
+        )),
+        TriggerActionConfig::Sound => TriggerAction::Sound,
+        TriggerActionConfig::Copy { group } => {
+            if *group >= regex.captures_len() {
+                errors.push(format!(
+                    "trigger {:?} has no group {}",
+                    trigger.regex, group
+                ));
+                return None;
+            }
+            TriggerAction::Copy(*group)
+        }
+        TriggerActionConfig::Run { command } if command.is_empty() => {
+            errors.push(format!("trigger {:?} has an empty command", trigger.regex));
+            return None;
+        }
+        TriggerActionConfig::Run { command } => TriggerAction::Run(command.clone()),
+        TriggerActionConfig::Notify => TriggerAction::Notify,
+        TriggerActionConfig::Progress { group } => {
+            if *group >= regex.captures_len() {
+                errors.push(format!(
+                    "trigger {:?} has no group {}",
+                    trigger.regex, group
+                ));
+                return None;
+            }
+            TriggerAction::Progress(*group)
+        }
+    };
+    Some(watch::Trigger { regex, action })
+}
+
+fn parse_title_rule(rule: &TitleRuleConfig, errors: &mut Vec<String>) -> Option<titles::TitleRule> {
+    if rule.command.is_none() && rule.cwd.is_none() && rule.host.is_none() {
+        errors.push(format!(
+            "title rule {:?} needs a command, cwd or host to match",
+            rule.title
+        ));
+        return None;
+    }
+    let mut valid = true;
+    let mut regex = |regex: &Option<String>| {
+        let regex = regex.as_ref()?;
+        match Regex::new(regex) {
+            Ok(regex) => Some(regex),
+            Err(err) => {
+                errors.push(format!("invalid regex {:?}: {}", regex, err));
+                valid = false;
+                None
+            }
+        }
+    };
+    let (command, cwd, host) = (regex(&rule.command), regex(&rule.cwd), regex(&rule.host));
+    if !valid {
+        return None;
+    }
+    Some(titles::TitleRule {
+        command,
+        cwd,
+        host,
+        title: rule.title.clone(),
+    })
+}
+
+fn parse_host(host: &HostConfig, errors: &mut Vec<String>) -> Option<ssh::HostColors> {
+    let pattern = match ssh::host_pattern(&host.pattern) {
+        Ok(pattern) => pattern,
+        Err(err) => {
+            errors.push(format!("invalid host pattern {:?}: {}", host.pattern, err));
+            return None;
+        }
+    };
+    let mut color = |color: &Option<String>| {
+        let color = color.as_ref()?;
+        match hacks::parse_color(color) {
+            Ok(color) => Some(color),
+            Err(_) => {
+                errors.push(format!("invalid color {:?}", color));
+                None
+            }
+        }
+    };
+    Some(ssh::HostColors {
+        pattern,
+        background: color(&host.background),
+        tint: color(&host.tint),
+    })
+}
+
+fn parse_connection(connection: &ConnectionConfig, errors: &mut Vec<String>) -> ssh::Connection {
+    let mut color = |color: &Option<String>| {
+        let color = color.as_ref()?;
+        match hacks::parse_color(color) {
+            Ok(color) => Some(color),
+            Err(_) => {
+                errors.push(format!(
+                    "invalid color {:?} for connection {:?}",
+                    color, connection.name
+                ));
+                None
+            }
+        }
+    };
+    ssh::Connection {
+        name: connection.name.clone(),
+        destination: connection.host.clone(),
+        port: connection.port,
+        mosh: connection.mosh,
+        title: connection.title.clone(),
+        command: connection.command.clone(),
+        colors: tabs::Colors {
+            background: color(&connection.background),
+            tint: color(&connection.tint),
+        },
+    }
+}
+
+fn parse_kubernetes(
+    kubernetes: &KubernetesConfig,
+    errors: &mut Vec<String>,
+) -> kubernetes::Settings {
+    let mut color = |color: &Option<String>| {
+        let color = color.as_ref()?;
+        match hacks::parse_color(color) {
+            Ok(color) => Some(color),
+            Err(_) => {
+                errors.push(format!("invalid color {:?} in [kubernetes]", color));
+                None
+            }
+        }
+    };
+    kubernetes::Settings {
+        kubectl: kubernetes.kubectl.clone(),
+        shell: kubernetes.shell.clone(),
+        contexts: kubernetes.contexts.clone(),
+        colors: tabs::Colors {
+            background: color(&kubernetes.background),
+            tint: color(&kubernetes.tint),
+        },
+    }
+}
+
+fn parse_match(pattern: &MatchConfig, errors: &mut Vec<String>) -> Option<links::Pattern> {
+    match links::compile(&pattern.name, &pattern.regex) {
+        Ok(compiled) => Some(compiled),
+        Err(err) => {
+            errors.push(format!(
+                "invalid regex {:?} for match {:?}: {}",
+                pattern.regex, pattern.name, err
+            ));
+            None
+        }
+    }
+}
+
+fn parse_highlight(
+    highlight: &HighlightConfig,
+    errors: &mut Vec<String>,
+) -> Option<marks::HighlightRule> {
+    match Regex::new(&highlight.regex) {
+        Ok(regex) => Some(marks::HighlightRule {
+            regex,
+            color: parse_color(
+                &highlight.color,
+                hacks::parse_color(&default_highlight_color()).unwrap(),
+                errors,
+            ),
+        }),
+        Err(err) => {
+            errors.push(format!(
+                "invalid highlight regex {:?}: {}",
+                highlight.regex, err
+            ));
+            None
+        }
+    }
+}
+
+fn parse_key(key: &KeyConfig, errors: &mut Vec<String>) -> Option<KeyBinding> {
+    let (keyval, modifiers) = match keys::parse_key(&key.key) {
+        Some(parsed) => parsed,
+        None => {
+            errors.push(format!("invalid key {:?}", key.key));
+            return None;
+        }
+    };
+    let bytes = match &key.action {
+        KeyActionConfig::SendText(text) => text.clone().into_bytes(),
+        KeyActionConfig::SendEscape(text) => match keys::unescape(text) {
+            Ok(bytes) => bytes,
+            Err(err) => {
+                errors.push(format!("key {:?}: {}", key.key, err));
+                return None;
+            }
+        },
+    };
+    Some(KeyBinding::new(keyval, modifiers, bytes))
+}
+
+impl TerminalConfig {
+    // invalid values are replaced with defaults and reported in the returned list
+    pub fn from_config(config: &Config) -> (TerminalConfig, Vec<String>) {
+        let mut errors = Vec::new();
+        let mut terminal_config = TerminalConfig {
+            background: parse_color(&config.colors.background, gdk::RGBA::black(), &mut errors),
+            foreground: parse_color(&config.colors.foreground, gdk::RGBA::white(), &mut errors),
+            font: {
+                let mut font = FontDescription::new();
+                let family = config.font_family.pango_family();
+                if family.is_empty() {
+                    errors.push("font_family is empty".to_string());
+                }
+                font.set_family(&family);
+                font.set_size(pango::SCALE * config.font_size as i32);
+                font
+            },
+            palette: config
+                .colors
+                .palette
+                .iter()
+                .map(|color| parse_color(color, gdk::RGBA::black(), &mut errors))
+                .collect(),
+            cursor: config
+                .colors
+                .cursor
+                .as_ref()
+                .map(|color| parse_color(color, gdk::RGBA::white(), &mut errors)),
+            cursor_foreground: config
+                .colors
+                .cursor_foreground
+                .as_ref()
+                .map(|color| parse_color(color, gdk::RGBA::black(), &mut errors)),
+            search_url: config.search_url.clone(),
+            title_template: config.title_template.clone(),
+            title_path: config.title_path,
+            badge_template: config.badge_template.clone(),
+            per_tab_zoom: config.per_tab_zoom,
+            spawn_timeout: config.spawn_timeout.map(Duration::from_secs),
+            close_on_exit: config.close_on_exit,
+            close_delay: Duration::from_secs(config.close_delay),
+            scroll_unit_is_pixels: config.scroll_unit_is_pixels,
+            scrollback_lines: config.scrollback_lines,
+            scrollback_storage: config.scrollback_storage,
+            new_tab_position: config.new_tab_position,
+            focus_new_tab: config.focus_new_tab,
+            inherit_env: config.inherit_env.clone(),
+            ssh_title_template: config.ssh_title_template.clone(),
+            git_title: config.git_title,
+            dim_inactive: config.dim_inactive.min(100),
+            pane_resize_step: config.pane_resize_step,
+            long_command: Duration::from_secs(config.long_command),
+            unlock_command: config.unlock_command.clone(),
+            secret_backend: config.secret_backend,
+            lock_idle: config.lock_idle.map(Duration::from_secs),
+            busy_cpu: config.busy_cpu,
+            hibernate_after: config.hibernate_after.map(Duration::from_secs),
+            focus_follows_output: config.focus_follows_output,
+            text_shaping: config.text_shaping,
+            bidi: config.bidi,
+            text_blink_mode: config.text_blink_mode,
+            cursor_blink: config.cursor_blink,
+            cursor_blink_timeout: config.cursor_blink_timeout,
+            reduce_motion: config.reduce_motion,
+            middle_click_command: config.middle_click_command,
+            input_method: config.input_method.clone(),
+            alt_sends_escape: config.alt_sends_escape,
+            use_physical_keys: config.use_physical_keys,
+            ctrl_c_copies: config.ctrl_c_copies,
+            application_cursor: config.application_cursor,
+            application_keypad: config.application_keypad,
+            big_cursor: config.big_cursor,
+            cursor_crosshair: config.cursor_crosshair,
+            minimum_contrast: if (1.0..=21.0).contains(&config.minimum_contrast) {
+                config.minimum_contrast
+            } else {
+                errors.push(format!(
+                    "minimum_contrast must be between 1 and 21, not {}",
+                    config.minimum_contrast
+                ));
+                default_minimum_contrast()
+            },
+            triggers: config
+                .triggers
+                .iter()
+                .filter_map(|trigger| parse_trigger(trigger, &mut errors))
+                .collect(),
+            highlights: config
+                .highlights
+                .iter()
+                .filter_map(|highlight| parse_highlight(highlight, &mut errors))
+                .collect(),
+            redact: config.redacted,
+            screenshot_area: config.screenshot_area,
+            print_colors: config.print_colors,
+            detachable: config.detachable,
+            redactions: redact::builtin()
+                .into_iter()
+                .chain(config.redact.iter().filter_map(|pattern| {
+                    Regex::new(pattern)
+                        .map_err(|err| {
+                            errors.push(format!("invalid redact regex {:?}: {}", pattern, err))
+                        })
+                        .ok()
+                }))
+                .collect(),
+            keys: config
+                .keys
+                .iter()
+                .filter_map(|key| parse_key(key, &mut errors))
+                .collect(),
+            hosts: config
+                .hosts
+                .iter()
+                .filter_map(|host| parse_host(host, &mut errors))
+                .collect(),
+            title_rules: config
+                .title_rules
+                .iter()
+                .filter_map(|rule| parse_title_rule(rule, &mut errors))
+                .collect(),
+            connections: config
+                .connections
+                .iter()
+                .map(|connection| parse_connection(connection, &mut errors))
+                .collect(),
+            kubernetes: config
+                .kubernetes
+                .as_ref()
+                .map(|kubernetes| parse_kubernetes(kubernetes, &mut errors)),
+            scroll_speed: if config.performance.scroll_speed > 0.0 {
+                config.performance.scroll_speed
+            } else {
+                errors.push(format!(
+                    "scroll_speed must be more than 0, not {}",
+                    config.performance.scroll_speed
+                ));
+                default_scroll_speed()
+            },
+            sync_to_vblank: config.performance.sync_to_vblank,
+            low_latency: config.performance.low_latency,
+            matches: links::builtin()
+                .into_iter()
+                .chain(
+                    config
+                        .matches
+                        .iter()
+                        .filter_map(|pattern| parse_match(pattern, &mut errors)),
+                )
+                .collect(),
+            clicks: config
+                .clicks
+                .iter()
+                .cloned()
+                .chain(links::default_clicks(config.link_activation))
+                .filter(|click| links::allowed(click, config.link_activation))
+                .collect(),
+            snippets: config
+                .snippets
+                .iter()
+                .map(|(name, text)| (name.clone(), text.clone()))
+                .collect(),
+        };
+        for (name, text) in &terminal_config.snippets {
+            if text.matches(snippets::CURSOR).count() > 1 {
+                errors.push(format!(
+                    "snippet {:?} has more than one {}, the first is used",
+                    name,
+                    snippets::CURSOR
+                ));
+            }
+        }
+        for click in &terminal_config.clicks {
+            if !terminal_config
+                .matches
+                .iter()
+                .any(|pattern| pattern.name == click.class)
+            {
+                errors.push(format!("[[clicks]] for unknown match {:?}", click.class));
+            }
+        }
+        let background = terminal_config.background;
+        let given = terminal_config.palette.len();
+        if crate::luminance(&background) > 0.5 && given < LIGHT_PALETTE.len() {
+            terminal_config.palette.extend(
+                LIGHT_PALETTE[given..]
+                    .iter()
+                    .map(|color| parse_color(color, gdk::RGBA::black(), &mut errors)),
+            );
+        }
+        check_contrast(&terminal_config, given, &mut errors);
+        (terminal_config, errors)
+    }
+}
+
+// as they'll look after minimum_contrast; the black and white slots are left
+// out, one of them is meant to blend in
+fn check_contrast(config: &TerminalConfig, given: usize, errors: &mut Vec<String>) {
+    let background = &config.background;
+    let ratio = crate::contrast(&config.foreground, background).max(config.minimum_contrast);
+    if ratio < MIN_FOREGROUND_CONTRAST {
+        errors.push(format!(
+            "foreground and background are hard to tell apart ({:.1}:1), \
+             raise minimum_contrast or pick other colors",
+            ratio
+        ));
+    }
+    for (slot, color) in config.palette.iter().enumerate().take(given.min(16)) {
+        if matches!(slot, 0 | 7 | 8 | 15) {
+            continue;
+        }
+        let ratio = crate::contrast(color, background).max(config.minimum_contrast);
+        if ratio < MIN_PALETTE_CONTRAST {
+            errors.push(format!(
+                "palette color {} is barely visible on the background ({:.1}:1)",
+                slot, ratio
+            ));
+        }
+    }
+}
+
+// never fails, problems are returned as messages to show to the user
+pub fn load(path: &Path) -> (TerminalConfig, Vec<String>) {
+    let mut errors = Vec::new();
+    let config = match Config::from_file(path) {
+        Err(err) => {
+            errors.push(format!(
+                "error reading config file at {}: {}",
+                path.display(),
+                err
+            ));
+            Config::from_str(DEFAULT_CONFIG).unwrap()
+        }
+        Ok(config) => config,
+    };
+    let mut config = config;
+    if let Some(name) = &config.theme {
+        match theme::load(name) {
+            Ok(colors) => config.colors = colors,
+            Err(err) => errors.push(format!("error loading theme {:?}: {}", name, err)),
+        }
+    }
+    let (terminal_config, config_errors) = TerminalConfig::from_config(&config);
+    errors.extend(config_errors);
+    (terminal_config, errors)
+}
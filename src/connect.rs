@@ -0,0 +1,124 @@
+// "Connect…" and `pterm ssh`: a new tab running ssh, or mosh, to one of the
+// [[connections]] in the config or to whatever host is typed in. A connection
+// gives its tab a title and colors of its own and may run a command there.
+
+use gtk::prelude::*;
+use gtk::subclass::prelude::*;
+
+use crate::ssh::{self, Connection};
+use crate::{Term, TermImpl};
+
+// by name first, then by [user@]host
+fn find(term: &Term, name: &str) -> Option<Connection> {
+    let config = TermImpl::from_instance(term).config.borrow();
+    let found = config
+        .connections
+        .iter()
+        .find(|connection| connection.name == name)
+        .or_else(|| {
+            config
+                .connections
+                .iter()
+                .find(|connection| connection.destination == name)
+        })
+        .cloned();
+    found
+}
+
+// `pterm ssh` arguments: a connection or destination by itself, or a command
+// line for ssh, which gets the connection of its host if there is one
+pub fn open(term: &Term, args: &[String]) {
+    if let [name] = args {
+        let connection = find(term, name).unwrap_or_else(|| Connection::to(name, false));
+        launch(term, &connection.argv(), Some(connection));
+        return;
+    }
+    let argv: Vec<String> = std::iter::once("ssh".to_string())
+        .chain(args.iter().cloned())
+        .collect();
+    let connection = ssh::parse(&argv).and_then(|remote| {
+        let destination = match &remote.user {
+            Some(user) => format!("{}@{}", user, remote.host),
+            None => remote.host.clone(),
+        };
+        find(term, &destination).or_else(|| find(term, &remote.host))
+    });
+    launch(term, &argv, connection);
+}
+
+fn launch(term: &Term, argv: &[String], connection: Option<Connection>) {
+    let terminal = term.add_new_tab_with_command(Some(argv));
+    if let Some(connection) = connection {
+        let imp = TermImpl::from_instance(term);
+        if let Some(meta) = imp.tabs.borrow_mut().by_terminal_mut(&terminal) {
+            if connection.title.is_some() {
+                meta.title = connection.title.clone();
+            }
+            if !connection.colors.is_empty() {
+                meta.colors = Some(connection.colors);
+            }
+        }
+        term.apply_colors(&terminal);
+        term.refresh_label(&terminal);
+    }
+    terminal.grab_focus();
+}
+
+pub fn show(term: &Term) {
+    let connections = TermImpl::from_instance(term)
+        .config
+        .borrow()
+        .connections
+        .clone();
+    let dialog = gtk::Dialog::with_buttons(
+        Some("Connect"),
+        Some(term),
+        gtk::DialogFlags::MODAL | gtk::DialogFlags::DESTROY_WITH_PARENT,
+        &[
+            ("Cancel", gtk::ResponseType::Cancel),
+            ("Connect", gtk::ResponseType::Ok),
+        ],
+    );
+    dialog.set_default_response(gtk::ResponseType::Ok);
+    let combo = gtk::ComboBoxText::with_entry();
+    for connection in &connections {
+        combo.append(Some(&connection.name), &connection.name);
+    }
+    let entry = match combo
+        .child()
+        .and_then(|child| child.downcast::<gtk::Entry>().ok())
+    {
+        Some(entry) => entry,
+        None => return,
+    };
+    entry.set_placeholder_text(Some("connection or [user@]host"));
+    entry.set_activates_default(true);
+    let mosh = gtk::CheckButton::with_label("Use mosh");
+    // picking a connection shows how it connects
+    combo.connect_changed(glib::clone!(@weak mosh => move |combo| {
+        let picked = combo
+            .active_id()
+            .and_then(|id| connections.iter().find(|connection| connection.name == id.as_str()));
+        if let Some(connection) = picked {
+            mosh.set_active(connection.mosh);
+        }
+    }));
+    let content = dialog.content_area();
+    content.pack_start(&combo, false, false, 6);
+    content.pack_start(&mosh, false, false, 6);
+
+    dialog.connect_response(
+        glib::clone!(@weak term, @weak entry, @weak mosh => move |dialog, response| {
+            let name = entry.text().trim().to_string();
+            let mosh = mosh.is_active();
+            dialog.close();
+            if response != gtk::ResponseType::Ok || name.is_empty() {
+                return;
+            }
+            let mut connection = find(&term, &name).unwrap_or_else(|| Connection::to(&name, mosh));
+            connection.mosh = mosh;
+            launch(&term, &connection.argv(), Some(connection));
+        }),
+    );
+    dialog.show_all();
+}
@@ -0,0 +1,166 @@
+// "New Tab in Container…": the running podman and docker containers, toolbox
+// ones among them, to open a shell in. The container's name is kept for
+// {container} in title_template.
+
+use gtk::prelude::*;
+use gtk::subclass::prelude::*;
+
+use crate::{picker, Term, TermImpl};
+
+// podman marks the containers toolbox made with this
+const TOOLBOX_LABEL: &str = "com.github.containers.toolbox";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Engine {
+    Podman,
+    Docker,
+    Toolbox,
+}
+
+impl Engine {
+    fn name(self) -> &'static str {
+        match self {
+            Engine::Podman => "podman",
+            Engine::Docker => "docker",
+            Engine::Toolbox => "toolbox",
+        }
+    }
+}
+
+struct Container {
+    name: String,
+    engine: Engine,
+}
+
+impl Container {
+    // the container's own shell when it says which, sh otherwise
+    fn command(&self) -> Vec<String> {
+        let shell = ["sh", "-c", "exec \"${SHELL:-sh}\""];
+        let argv: Vec<&str> = match self.engine {
+            Engine::Toolbox => vec!["toolbox", "enter", self.name.as_str()],
+            Engine::Podman | Engine::Docker => {
+                [self.engine.name(), "exec", "-it", self.name.as_str()]
+                    .iter()
+                    .chain(&shell)
+                    .copied()
+                    .collect()
+            }
+        };
+        argv.into_iter().map(String::from).collect()
+    }
+}
+
+// `program`'s output lines, none when the engine isn't there or isn't
+// running; without blocking, a docker daemon can take its time to answer
+fn lines(program: &'static str, args: &[&str], done: impl FnOnce(Vec<String>) + 'static) {
+    let argv: Vec<&std::ffi::OsStr> = std::iter::once(program)
+        .chain(args.iter().copied())
+        .map(std::ffi::OsStr::new)
+        .collect();
+    let flags = gio::SubprocessFlags::STDOUT_PIPE | gio::SubprocessFlags::STDERR_PIPE;
+    let process = match gio::Subprocess::newv(&argv, flags) {
+        Ok(process) => process,
+        Err(err) => {
+            log::debug!("can't run {}: {}", program, err);
+            done(Vec::new());
+            return;
+        }
+    };
+    process.communicate_utf8_async(
+        None,
+        None::<&gio::Cancellable>,
+        glib::clone!(@strong process => move |result| {
+            let lines = match result {
+                Ok((stdout, _)) if process.is_successful() => stdout
+                    .as_deref()
+                    .unwrap_or_default()
+                    .lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty())
+                    .map(String::from)
+                    .collect(),
+                Ok((_, stderr)) => {
+                    log::debug!("{} failed: {}", program, stderr.as_deref().unwrap_or_default().trim());
+                    Vec::new()
+                }
+                Err(err) => {
+                    log::debug!("{} failed: {}", program, err);
+                    Vec::new()
+                }
+            };
+            done(lines);
+        }),
+    );
+}
+
+// podman's, toolbox ones among them, then docker's
+fn running(done: impl FnOnce(Vec<Container>) + 'static) {
+    lines(
+        "podman",
+        &["ps", "--format", "{{.Names}} {{.Labels}}"],
+        move |podman| {
+            let mut containers: Vec<Container> = podman
+                .iter()
+                .map(|line| {
+                    let (name, labels) = line.split_once(' ').unwrap_or((line, ""));
+                    Container {
+                        name: name.to_string(),
+                        engine: if labels.contains(TOOLBOX_LABEL) {
+                            Engine::Toolbox
+                        } else {
+                            Engine::Podman
+                        },
+                    }
+                })
+                .collect();
+            lines("docker", &["ps", "--format", "{{.Names}}"], move |docker| {
+                // docker may well be podman under another name
+                for name in docker {
+                    if !containers.iter().any(|container| container.name == name) {
+                        containers.push(Container {
+                            name,
+                            engine: Engine::Docker,
+                        });
+                    }
+                }
+                done(containers);
+            });
+        },
+    );
+}
+
+pub fn show(term: &Term) {
+    running(glib::clone!(@weak term => move |containers| pick(&term, containers)));
+}
+
+fn pick(term: &Term, containers: Vec<Container>) {
+    if containers.is_empty() {
+        term.show_warning("no running podman, docker or toolbox containers");
+        return;
+    }
+    let items = containers
+        .iter()
+        .map(|container| picker::Item {
+            label: container.name.clone(),
+            detail: Some(container.engine.name().to_string()),
+        })
+        .collect();
+    picker::show(
+        term,
+        "New Tab in Container",
+        items,
+        glib::clone!(@weak term => move |index| {
+            open(&term, &containers[index]);
+        }),
+    );
+}
+
+fn open(term: &Term, container: &Container) {
+    let terminal = term.add_new_tab_with_command(Some(&container.command()));
+    let imp = TermImpl::from_instance(term);
+    if let Some(meta) = imp.tabs.borrow_mut().by_terminal_mut(&terminal) {
+        meta.container = Some(container.name.clone());
+    }
+    term.refresh_label(&terminal);
+    terminal.grab_focus();
+}
@@ -0,0 +1,155 @@
+// on a panic the open tabs are saved like a layout and the backtrace is
+// written next to them; the next start offers both back, the report with a
+// button to copy it into a bug report
+
+use std::backtrace::Backtrace;
+use std::cell::RefCell;
+use std::path::PathBuf;
+
+use gtk::prelude::*;
+use gtk::subclass::prelude::*;
+
+use crate::layout::{self, Layout};
+use crate::{version, Term, TermImpl};
+
+const REPORT: &str = "crash/report.txt";
+const SESSION: &str = "crash/session.toml";
+
+thread_local! {
+    // windows whose tabs are saved on a panic, only known to the gtk thread
+    static WINDOWS: RefCell<Vec<glib::WeakRef<Term>>> = RefCell::new(Vec::new());
+}
+
+fn place(file: &str) -> Option<PathBuf> {
+    xdg::BaseDirectories::with_prefix("pterm")
+        .ok()?
+        .place_data_file(file)
+        .ok()
+}
+
+fn find(file: &str) -> Option<PathBuf> {
+    xdg::BaseDirectories::with_prefix("pterm")
+        .ok()?
+        .find_data_file(file)
+}
+
+pub fn install() {
+    let default = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        default(info);
+        let report = format!(
+            "pterm {}\n{}\n\n{}",
+            version::version(),
+            info,
+            Backtrace::force_capture()
+        );
+        if let Some(path) = place(REPORT) {
+            if let Err(err) = std::fs::write(&path, report) {
+                eprintln!("error writing crash report to {}: {}", path.display(), err);
+            }
+        }
+        if let Some(session) = session() {
+            if let Some(path) = place(SESSION) {
+                let _ = toml::to_string(&session).map(|text| std::fs::write(path, text));
+            }
+        }
+    }));
+}
+
+pub fn watch(term: &Term) {
+    WINDOWS.with(|windows| {
+        let mut windows = windows.borrow_mut();
+        windows.retain(|window| window.upgrade().is_some());
+        windows.push(term.downgrade());
+    });
+}
+
+// the tabs of every window, unless the panic left them borrowed: another
+// panic from inside the hook would abort before anything is written
+fn session() -> Option<Layout> {
+    let windows = WINDOWS
+        .try_with(|windows| windows.try_borrow().ok().map(|windows| windows.clone()))
+        .ok()??;
+    let mut session = Layout { tabs: Vec::new() };
+    for term in windows.iter().filter_map(|window| window.upgrade()) {
+        let imp = TermImpl::from_instance(&term);
+        if imp.tabs.try_borrow_mut().is_err()
+            || imp.notebook.try_borrow_mut().is_err()
+            || imp.config.try_borrow_mut().is_err()
+        {
+            return None;
+        }
+        session.tabs.extend(layout::snapshot(&term).tabs);
+    }
+    Some(session)
+}
+
+// shows what the last crash left behind, once
+pub fn check(term: &Term) {
+    let report = match find(REPORT) {
+        Some(path) => {
+            let report = std::fs::read_to_string(&path).unwrap_or_default();
+            let _ = std::fs::remove_file(path);
+            report
+        }
+        None => return,
+    };
+    let session: Option<Layout> = find(SESSION).and_then(|path| {
+        let session = std::fs::read(&path)
+            .ok()
+            .and_then(|text| toml::from_slice(&text).ok());
+        let _ = std::fs::remove_file(path);
+        session
+    });
+    let session = session.filter(|session| !session.tabs.is_empty());
+
+    let dialog = gtk::Dialog::with_buttons(
+        Some("pterm crashed"),
+        Some(term),
+        gtk::DialogFlags::DESTROY_WITH_PARENT,
+        &[
+            ("Copy to Clipboard", gtk::ResponseType::Apply),
+            ("Close", gtk::ResponseType::Close),
+        ],
+    );
+    if session.is_some() {
+        dialog.add_button("Restore Tabs", gtk::ResponseType::Accept);
+        dialog.set_default_response(gtk::ResponseType::Accept);
+    }
+    dialog.set_default_size(600, 400);
+
+    let message = gtk::Label::new(Some(
+        "pterm quit unexpectedly last time. \
+         Please include the report below when filing a bug.",
+    ));
+    message.set_line_wrap(true);
+    message.set_xalign(0.0);
+    let text = gtk::TextView::builder()
+        .editable(false)
+        .monospace(true)
+        .wrap_mode(gtk::WrapMode::WordChar)
+        .build();
+    text.buffer().unwrap().set_text(&report);
+    let scrolled = gtk::ScrolledWindow::builder().child(&text).build();
+
+    let content = dialog.content_area();
+    content.set_spacing(6);
+    content.pack_start(&message, false, false, 6);
+    content.pack_start(&scrolled, true, true, 0);
+
+    dialog.connect_response(
+        glib::clone!(@weak term => move |dialog, response| match response {
+            gtk::ResponseType::Apply => {
+                gtk::Clipboard::get(&gdk::SELECTION_CLIPBOARD).set_text(&report);
+            }
+            gtk::ResponseType::Accept => {
+                if let Some(session) = &session {
+                    layout::restore(&term, session);
+                }
+                dialog.close();
+            }
+            _ => dialog.close(),
+        }),
+    );
+    dialog.show_all();
+}
@@ -0,0 +1,226 @@
+// `pterm ctl <command>` talks to the running pterm over a unix socket in
+// $XDG_RUNTIME_DIR/pterm; one request line in, the reply until the connection
+// closes. The socket is served from a thread, the answer is put together on
+// the main thread where the windows are.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+use std::sync::mpsc;
+
+use anyhow::{bail, Error};
+use gtk::prelude::*;
+use gtk::subclass::prelude::*;
+use serde::Serialize;
+use vte::TerminalExt;
+
+use crate::{history, lock, platform, Term, TermImpl, ALERT_CLASS};
+
+const SOCKET: &str = "ctl.sock";
+const USAGE: &str = "usage: pterm ctl list-tabs [--json]";
+// connections are served one at a time, a client that doesn't send its
+// request or read the reply only holds up the others this long
+const CLIENT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(2);
+
+// one tab, as seen from its focused pane
+#[derive(Serialize)]
+struct Tab {
+    window: usize,
+    index: usize,
+    id: String,
+    title: Option<String>,
+    cwd: Option<String>,
+    pid: Option<u32>,
+    command: Option<String>,
+    state: &'static str,
+    active: bool,
+    alert: bool,
+    progress: Option<u8>,
+}
+
+fn socket_path() -> Result<PathBuf, Error> {
+    Ok(xdg::BaseDirectories::with_prefix("pterm")?.place_runtime_file(SOCKET)?)
+}
+
+// the client side, prints the reply
+pub fn request(args: &[String]) -> Result<(), Error> {
+    match args.first().map(String::as_str) {
+        Some("list-tabs") => {}
+        _ => bail!(USAGE),
+    }
+    let path = socket_path()?;
+    let mut stream = UnixStream::connect(&path)
+        .map_err(|err| anyhow::anyhow!("can't connect to {}: {}", path.display(), err))?;
+    writeln!(stream, "{}", args.join(" "))?;
+    let mut reply = String::new();
+    stream.read_to_string(&mut reply)?;
+    print!("{}", reply);
+    Ok(())
+}
+
+// listens for as long as the application runs, unless another instance already
+// does (NON_UNIQUE ones don't get a socket)
+pub fn serve(app: &gtk::Application) {
+    let path = match socket_path() {
+        Ok(path) => path,
+        Err(err) => {
+            log::warn!("no control socket: {}", err);
+            return;
+        }
+    };
+    if UnixStream::connect(&path).is_ok() {
+        log::info!("{} is served by another pterm", path.display());
+        return;
+    }
+    let _ = std::fs::remove_file(&path);
+    let listener = match UnixListener::bind(&path) {
+        Ok(listener) => listener,
+        Err(err) => {
+            log::warn!("can't listen on {}: {}", path.display(), err);
+            return;
+        }
+    };
+    log::info!("listening on {}", path.display());
+
+    let (sender, receiver) = glib::MainContext::channel(glib::PRIORITY_DEFAULT);
+    let app_ref = app.downgrade();
+    receiver.attach(
+        None,
+        move |(line, reply): (String, mpsc::Sender<String>)| {
+            let app = match app_ref.upgrade() {
+                Some(app) => app,
+                None => return glib::Continue(false),
+            };
+            let _ = reply.send(answer(&app, &line));
+            glib::Continue(true)
+        },
+    );
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let timeouts = stream
+                .set_read_timeout(Some(CLIENT_TIMEOUT))
+                .and_then(|_| stream.set_write_timeout(Some(CLIENT_TIMEOUT)));
+            if let Err(err) = timeouts {
+                log::warn!("can't set a timeout on a control connection: {}", err);
+                continue;
+            }
+            let mut line = String::new();
+            if BufReader::new(&stream).read_line(&mut line).is_err() {
+                continue;
+            }
+            let (reply, answered) = mpsc::channel();
+            if sender.send((line.trim().to_string(), reply)).is_err() {
+                break;
+            }
+            if let Ok(answer) = answered.recv() {
+                let _ = (&stream).write_all(answer.as_bytes());
+            }
+        }
+    });
+    app.connect_shutdown(move |_| {
+        let _ = std::fs::remove_file(&path);
+    });
+}
+
+fn answer(app: &gtk::Application, line: &str) -> String {
+    let args: Vec<&str> = line.split_whitespace().collect();
+    match args.as_slice() {
+        ["list-tabs"] => tabs(app).iter().map(text).collect(),
+        ["list-tabs", "--json"] => match serde_json::to_string(&tabs(app)) {
+            Ok(json) => format!("{}\n", json),
+            Err(err) => format!("can't encode the tabs: {}\n", err),
+        },
+        _ => format!("{}\n", USAGE),
+    }
+}
+
+fn tabs(app: &gtk::Application) -> Vec<Tab> {
+    let windows = app
+        .windows()
+        .into_iter()
+        .filter_map(|window| window.downcast::<Term>().ok());
+    let mut tabs = Vec::new();
+    for (window, term) in windows.enumerate() {
+        let notebook = term.notebook();
+        for (index, page) in notebook.children().iter().enumerate() {
+            if let Some(tab) = describe(&term, page, window, index) {
+                tabs.push(tab);
+            }
+        }
+    }
+    tabs
+}
+
+fn describe(term: &Term, page: &gtk::Widget, window: usize, index: usize) -> Option<Tab> {
+    let imp = TermImpl::from_instance(term);
+    let terminal = term.focused_terminal(page)?;
+    let tabs = imp.tabs.borrow();
+    let id = tabs.id(&terminal)?;
+    let meta = tabs.get(id)?;
+    let pid = meta.live_pid();
+    let foreground = terminal
+        .pty()
+        .and_then(|pty| platform::foreground_pid(pty.fd()));
+    let command = match &meta.command {
+        Some((command, _)) => Some(command.clone()),
+        None => foreground
+            .and_then(platform::process_cmdline)
+            .map(|argv| argv.join(" ")),
+    };
+    let state = if meta.exit_status.is_some() {
+        "exited"
+    } else if lock::is_locked(term, page) {
+        "locked"
+    } else if meta.paused {
+        "paused"
+    } else if meta.hibernating {
+        "hibernating"
+    } else if meta.command.is_some() || (foreground.is_some() && foreground != pid) {
+        "running"
+    } else {
+        "idle"
+    };
+    let title = meta
+        .title
+        .clone()
+        .or_else(|| terminal.window_title().map(|title| title.to_string()));
+    let cwd = terminal
+        .current_directory_uri()
+        .and_then(|uri| gio::File::for_uri(&uri).path())
+        .or_else(|| meta.cwd.clone())
+        .map(|cwd| cwd.display().to_string());
+    let active = term.is_active() && term.active_page().as_ref() == Some(page);
+    let alert = term
+        .notebook()
+        .tab_label(page)
+        .map(|label| label.style_context().has_class(ALERT_CLASS))
+        .unwrap_or(false);
+    Some(Tab {
+        window,
+        index,
+        id: id.to_string(),
+        title,
+        cwd,
+        pid,
+        command,
+        state,
+        active,
+        alert,
+        progress: meta.progress,
+    })
+}
+
+// tab separated: window, index, id, state, pid, title, cwd; tabs, newlines
+// and backslashes in the title and cwd escaped as in the history file
+fn text(tab: &Tab) -> String {
+    format!(
+        "{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+        tab.window,
+        tab.index,
+        tab.id,
+        tab.state,
+        tab.pid.map(|pid| pid.to_string()).unwrap_or_default(),
+        history::escape(tab.title.as_deref().unwrap_or_default()),
+        history::escape(tab.cwd.as_deref().unwrap_or_default()),
+    )
+}
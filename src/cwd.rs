@@ -0,0 +1,150 @@
+// Working directory of another process, each platform has its own way to ask.
+
+use std::path::{Component, Path, PathBuf};
+
+use crate::config::TitlePath;
+
+pub trait Backend {
+    fn cwd(&self, pid: u32) -> Option<PathBuf>;
+}
+
+// Linux, and the BSDs that mount a linux-compatible /proc
+#[cfg(not(any(target_os = "macos", target_os = "freebsd", windows)))]
+pub struct Procfs;
+
+#[cfg(not(any(target_os = "macos", target_os = "freebsd", windows)))]
+impl Backend for Procfs {
+    fn cwd(&self, pid: u32) -> Option<PathBuf> {
+        std::fs::read_link(format!("/proc/{}/cwd", pid)).ok()
+    }
+}
+
+#[cfg(target_os = "macos")]
+pub struct ProcPidInfo;
+
+#[cfg(target_os = "macos")]
+impl Backend for ProcPidInfo {
+    fn cwd(&self, pid: u32) -> Option<PathBuf> {
+        use std::ffi::CStr;
+        use std::mem::{size_of, MaybeUninit};
+
+        let mut info = MaybeUninit::<libc::proc_vnodepathinfo>::uninit();
+        let size = size_of::<libc::proc_vnodepathinfo>() as libc::c_int;
+        let info = unsafe {
+            let ret = libc::proc_pidinfo(
+                pid as libc::c_int,
+                libc::PROC_PIDVNODEPATHINFO,
+                0,
+                info.as_mut_ptr() as *mut libc::c_void,
+                size,
+            );
+            if ret != size {
+                return None;
+            }
+            info.assume_init()
+        };
+        let path =
+            unsafe { CStr::from_ptr(info.pvi_cdir.vip_path.as_ptr() as *const libc::c_char) };
+        Some(PathBuf::from(path.to_str().ok()?))
+    }
+}
+
+#[cfg(target_os = "freebsd")]
+pub struct Sysctl;
+
+#[cfg(target_os = "freebsd")]
+impl Backend for Sysctl {
+    fn cwd(&self, pid: u32) -> Option<PathBuf> {
+        use std::ffi::CStr;
+        use std::mem::{size_of, MaybeUninit};
+
+        let mib = [
+            libc::CTL_KERN,
+            libc::KERN_PROC,
+            libc::KERN_PROC_CWD,
+            pid as libc::c_int,
+        ];
+        let mut info = MaybeUninit::<libc::kinfo_file>::zeroed();
+        let mut len = size_of::<libc::kinfo_file>();
+        let info = unsafe {
+            let ret = libc::sysctl(
+                mib.as_ptr(),
+                mib.len() as libc::c_uint,
+                info.as_mut_ptr() as *mut libc::c_void,
+                &mut len,
+                std::ptr::null(),
+                0,
+            );
+            if ret != 0 || len == 0 {
+                return None;
+            }
+            info.assume_init()
+        };
+        let path = unsafe { CStr::from_ptr(info.kf_path.as_ptr()) };
+        Some(PathBuf::from(path.to_str().ok()?))
+    }
+}
+
+// there's no supported way to read another process' cwd on Windows
+#[cfg(windows)]
+pub struct Unsupported;
+
+#[cfg(windows)]
+impl Backend for Unsupported {
+    fn cwd(&self, _pid: u32) -> Option<PathBuf> {
+        None
+    }
+}
+
+pub fn backend() -> &'static dyn Backend {
+    #[cfg(target_os = "macos")]
+    return &ProcPidInfo;
+    #[cfg(target_os = "freebsd")]
+    return &Sysctl;
+    #[cfg(windows)]
+    return &Unsupported;
+    #[cfg(not(any(target_os = "macos", target_os = "freebsd", windows)))]
+    return &Procfs;
+}
+
+pub fn of(pid: u32) -> Option<PathBuf> {
+    backend().cwd(pid)
+}
+
+// `path` as it's shown in titles
+pub fn shorten(path: &Path, style: TitlePath) -> String {
+    let home = glib::home_dir();
+    let (prefix, rest) = match path.strip_prefix(&home) {
+        Ok(rest) if style != TitlePath::Full && home.parent().is_some() => ("~", rest),
+        _ => ("", path),
+    };
+    if style != TitlePath::Short {
+        return match prefix {
+            "" => path.display().to_string(),
+            _ if rest.as_os_str().is_empty() => "~".to_string(),
+            _ => format!("~/{}", rest.display()),
+        };
+    }
+    let names: Vec<String> = rest
+        .components()
+        .filter_map(|component| match component {
+            Component::Normal(name) => Some(name.to_string_lossy().into_owned()),
+            _ => None,
+        })
+        .collect();
+    let mut shortened = prefix.to_string();
+    for (index, name) in names.iter().enumerate() {
+        shortened.push('/');
+        if index + 1 == names.len() {
+            shortened.push_str(name);
+        } else {
+            // .config is .c rather than .
+            let letters = if name.starts_with('.') { 2 } else { 1 };
+            shortened.extend(name.chars().take(letters));
+        }
+    }
+    if shortened.is_empty() {
+        shortened.push('/');
+    }
+    shortened
+}
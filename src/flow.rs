@@ -0,0 +1,85 @@
+// Ctrl+S stops the output of a terminal until Ctrl+Q when the pty has
+// flow control on, which looks a lot like a hang; the tab says so and a
+// banner over the focused one has a button to resume
+
+use gtk::prelude::*;
+use gtk::subclass::prelude::*;
+use vte::TerminalExt;
+
+use crate::{platform, Term, TermImpl};
+
+const XOFF: char = '\x13';
+const XON: char = '\x11';
+
+const RESPONSE_RESUME: u16 = 1;
+
+// `text` is what the terminal is about to send to the child
+pub fn commit(term: &Term, terminal: &vte::Terminal, text: &str) {
+    let imp = TermImpl::from_instance(term);
+    let paused = match imp.tabs.borrow().by_terminal(terminal) {
+        Some(meta) => meta.paused,
+        None => return,
+    };
+    let flow = match terminal
+        .pty()
+        .and_then(|pty| platform::flow_control(pty.fd()))
+    {
+        Some(flow) => flow,
+        None => return,
+    };
+    // the last of XOFF and XON wins, with IXANY every other key resumes too
+    let now = match text.rfind(|c| c == XOFF || c == XON) {
+        Some(at) => flow.enabled && text[at..].starts_with(XOFF),
+        None if flow.any_resumes => false,
+        None => paused,
+    };
+    if now != paused {
+        set_paused(term, terminal, now);
+    }
+}
+
+fn set_paused(term: &Term, terminal: &vte::Terminal, paused: bool) {
+    let imp = TermImpl::from_instance(term);
+    if let Some(meta) = imp.tabs.borrow_mut().by_terminal_mut(terminal) {
+        meta.paused = paused;
+    }
+    term.refresh_label(terminal);
+    show_banner(term, terminal);
+}
+
+fn resume(term: &Term, terminal: &vte::Terminal) {
+    terminal.feed_child(XON.to_string().as_bytes());
+    set_paused(term, terminal, false);
+}
+
+// the banner is there while `terminal`, the focused one, is paused
+pub fn show_banner(term: &Term, terminal: &vte::Terminal) {
+    let imp = TermImpl::from_instance(term);
+    if let Some(banner) = imp.flow_banner.borrow_mut().take() {
+        banner.destroy();
+    }
+    let paused = imp
+        .tabs
+        .borrow()
+        .by_terminal(terminal)
+        .map(|meta| meta.paused)
+        .unwrap_or(false);
+    if !paused {
+        return;
+    }
+    let banner = term.banner(
+        gtk::MessageType::Info,
+        "Output is paused, Ctrl+S stops it and Ctrl+Q resumes it.",
+    );
+    banner.set_show_close_button(false);
+    banner.add_button("Resume", gtk::ResponseType::Other(RESPONSE_RESUME));
+    banner.connect_response(
+        glib::clone!(@weak term, @weak terminal => move |_, response| {
+            if response == gtk::ResponseType::Other(RESPONSE_RESUME) {
+                resume(&term, &terminal);
+                terminal.grab_focus();
+            }
+        }),
+    );
+    *imp.flow_banner.borrow_mut() = Some(banner);
+}
@@ -0,0 +1,62 @@
+// Which repository a directory is in and what's checked out there, read from
+// .git directly since a title shouldn't wait on a git process.
+
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Repo {
+    // the directory .git is in
+    pub root: PathBuf,
+    // the branch, or the start of the commit when detached
+    pub head: Option<String>,
+}
+
+impl Repo {
+    pub fn name(&self) -> String {
+        self.root
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| self.root.display().to_string())
+    }
+
+    // "pterm (master)"
+    pub fn title(&self) -> String {
+        match &self.head {
+            Some(head) => format!("{} ({})", self.name(), head),
+            None => self.name(),
+        }
+    }
+}
+
+// the repository `dir` is in, if any
+pub fn root(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .find(|dir| dir.join(".git").exists())
+        .map(Path::to_path_buf)
+}
+
+pub fn repo(dir: &Path) -> Option<Repo> {
+    let root = root(dir)?;
+    let dot_git = root.join(".git");
+    // worktrees and submodules have a file saying where the real one is
+    let git_dir = if dot_git.is_file() {
+        let contents = std::fs::read_to_string(&dot_git).ok()?;
+        let target = contents.trim().strip_prefix("gitdir:")?.trim();
+        root.join(target)
+    } else {
+        dot_git
+    };
+    let head = std::fs::read_to_string(git_dir.join("HEAD"))
+        .ok()
+        .and_then(|head| {
+            let head = head.trim();
+            match head.strip_prefix("ref:") {
+                Some(name) => {
+                    let name = name.trim();
+                    Some(name.strip_prefix("refs/heads/").unwrap_or(name).to_string())
+                }
+                None => head.get(..7).map(str::to_string),
+            }
+        });
+    Some(Repo { root, head })
+}
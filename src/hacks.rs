@@ -1,5 +1,6 @@
 use glib::translate::*;
 use gtk::prelude::*;
+use vte::TerminalExt;
 
 // TODO: in gtk master they have it, remove when updating
 pub fn parse_color(s: &str) -> Result<gdk::RGBA, glib::error::BoolError> {
@@ -37,3 +38,157 @@ pub fn set_child_property(
         )
     }
 }
+
+// a vte function newer than the oldest vte we run on, None when this one
+// doesn't have it; `F` has to be the function's exact pointer type
+unsafe fn vte_function<F: Copy>(name: &str) -> Option<F> {
+    let symbol = crate::platform::symbol(name)?;
+    Some(std::mem::transmute_copy(&symbol))
+}
+
+type SetBoolean = unsafe extern "C" fn(*mut vte_sys::VteTerminal, glib::ffi::gboolean);
+
+// vte >= 0.66, not in our bindings yet
+pub fn set_scroll_unit_is_pixels(terminal: &vte::Terminal, enable: bool) {
+    match unsafe { vte_function::<SetBoolean>("vte_terminal_set_scroll_unit_is_pixels") } {
+        Some(set) => unsafe { set(terminal.to_glib_none().0, enable.into_glib()) },
+        None => log::debug!("vte is older than 0.66, scrolling by lines"),
+    }
+}
+
+// vte >= 0.58, not in our bindings yet
+pub fn set_enable_shaping(terminal: &vte::Terminal, enable: bool) {
+    match unsafe { vte_function::<SetBoolean>("vte_terminal_set_enable_shaping") } {
+        Some(set) => unsafe { set(terminal.to_glib_none().0, enable.into_glib()) },
+        None => log::debug!("vte is older than 0.58, no text shaping setting"),
+    }
+}
+
+// vte >= 0.58, not in our bindings yet
+pub fn set_enable_bidi(terminal: &vte::Terminal, enable: bool) {
+    match unsafe { vte_function::<SetBoolean>("vte_terminal_set_enable_bidi") } {
+        Some(set) => unsafe { set(terminal.to_glib_none().0, enable.into_glib()) },
+        None => log::debug!("vte is older than 0.58, no bidi setting"),
+    }
+}
+
+// vte >= 0.52, not in our bindings yet; 0 never, 1 focused, 3 always
+pub fn set_text_blink_mode(terminal: &vte::Terminal, mode: i32) {
+    type Set = unsafe extern "C" fn(*mut vte_sys::VteTerminal, i32);
+    match unsafe { vte_function::<Set>("vte_terminal_set_text_blink_mode") } {
+        Some(set) => unsafe { set(terminal.to_glib_none().0, mode) },
+        None => log::debug!("vte is older than 0.52, text always blinks"),
+    }
+}
+
+// for text_range and text_range_colors, which pass no selection callback
+extern "C" {
+    fn vte_terminal_get_text_range(
+        terminal: *mut vte_sys::VteTerminal,
+        start_row: glib::ffi::glong,
+        start_col: glib::ffi::glong,
+        end_row: glib::ffi::glong,
+        end_col: glib::ffi::glong,
+        is_selected: vte_sys::VteSelectionFunc,
+        user_data: glib::ffi::gpointer,
+        attributes: *mut glib::ffi::GArray,
+    ) -> *mut std::os::raw::c_char;
+}
+
+// text from `start_col` of `start_row` to `end_col` of `end_row`, both
+// included, without going through the binding's selection callback
+pub fn text_range(
+    terminal: &vte::Terminal,
+    (start_row, start_col): (i64, i64),
+    (end_row, end_col): (i64, i64),
+) -> Option<String> {
+    unsafe {
+        let text = vte_terminal_get_text_range(
+            terminal.to_glib_none().0,
+            start_row as glib::ffi::glong,
+            start_col as glib::ffi::glong,
+            end_row as glib::ffi::glong,
+            end_col as glib::ffi::glong,
+            None,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+        );
+        from_glib_full::<_, Option<glib::GString>>(text).map(|text| text.to_string())
+    }
+}
+
+// VteCharAttributes, whose bitfields the generated vte-sys struct leaves out
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct CharAttributes {
+    row: glib::ffi::glong,
+    column: glib::ffi::glong,
+    fore: [u16; 3],
+    back: [u16; 3],
+    flags: std::os::raw::c_uint,
+}
+
+// foreground and background of a character, 16 bits per channel
+pub type CellColors = ([u16; 3], [u16; 3]);
+
+// like text_range, with the colors for every byte of the text; empty when this
+// vte doesn't fill them in anymore
+pub fn text_range_colors(
+    terminal: &vte::Terminal,
+    (start_row, start_col): (i64, i64),
+    (end_row, end_col): (i64, i64),
+) -> Option<(String, Vec<CellColors>)> {
+    unsafe {
+        let attributes = glib::ffi::g_array_new(
+            glib::ffi::GFALSE,
+            glib::ffi::GTRUE,
+            std::mem::size_of::<CharAttributes>() as u32,
+        );
+        let text = vte_terminal_get_text_range(
+            terminal.to_glib_none().0,
+            start_row as glib::ffi::glong,
+            start_col as glib::ffi::glong,
+            end_row as glib::ffi::glong,
+            end_col as glib::ffi::glong,
+            None,
+            std::ptr::null_mut(),
+            attributes,
+        );
+        let colors = match (*attributes).len as usize {
+            0 => Vec::new(),
+            len => std::slice::from_raw_parts((*attributes).data as *const CharAttributes, len)
+                .iter()
+                .map(|attributes| (attributes.fore, attributes.back))
+                .collect(),
+        };
+        glib::ffi::g_array_free(attributes, glib::ffi::GTRUE);
+        from_glib_full::<_, Option<glib::GString>>(text).map(|text| (text.to_string(), colors))
+    }
+}
+
+// whole rows `start_row..=end_row`, separated by newlines
+pub fn text_rows(terminal: &vte::Terminal, start_row: i64, end_row: i64) -> Option<String> {
+    text_range(terminal, (start_row, 0), (end_row, terminal.column_count()))
+}
+
+// wrapped lines come back joined from text_rows, this keeps rows apart
+pub fn text_row(terminal: &vte::Terminal, row: i64) -> Option<String> {
+    text_rows(terminal, row, row).map(|text| text.trim_end_matches('\n').to_string())
+}
+
+// the runtime version, not the one in the headers
+pub fn vte_version() -> (u32, u32, u32) {
+    extern "C" {
+        fn vte_get_major_version() -> u32;
+        fn vte_get_minor_version() -> u32;
+        fn vte_get_micro_version() -> u32;
+    }
+
+    unsafe {
+        (
+            vte_get_major_version(),
+            vte_get_minor_version(),
+            vte_get_micro_version(),
+        )
+    }
+}
@@ -0,0 +1,77 @@
+// tabs in the background with nothing happening in them for hibernate_after
+// skip the periodic work done for every tab and let go of what's cached for
+// them; their first output or a look at them wakes them up
+
+use std::time::{Duration, Instant};
+
+use gtk::prelude::*;
+use gtk::subclass::prelude::*;
+use vte::TerminalExt;
+
+use crate::{platform, split, Term, TermImpl};
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+pub fn watch(term: &Term) {
+    glib::timeout_add_local(
+        CHECK_INTERVAL,
+        glib::clone!(@weak term => @default-return glib::Continue(false), move || {
+            check(&term);
+            glib::Continue(true)
+        }),
+    );
+}
+
+// the shell itself is in the foreground, not a program it started
+fn at_prompt(terminal: &vte::Terminal, pid: Option<u32>) -> bool {
+    let foreground = terminal
+        .pty()
+        .and_then(|pty| platform::foreground_pid(pty.fd()));
+    pid.is_none() || foreground == pid
+}
+
+fn check(term: &Term) {
+    let imp = TermImpl::from_instance(term);
+    let after = match imp.config.borrow().hibernate_after {
+        Some(after) => after,
+        None => return,
+    };
+    let active = term.active_page();
+    let terminals: Vec<vte::Terminal> = imp.tabs.borrow().terminals().cloned().collect();
+    for terminal in &terminals {
+        if split::page(terminal) == active {
+            continue;
+        }
+        let mut tabs = imp.tabs.borrow_mut();
+        let meta = match tabs.by_terminal_mut(terminal) {
+            Some(meta) => meta,
+            None => continue,
+        };
+        if meta.hibernating
+            || meta.last_output.elapsed() < after
+            || meta.command.is_some()
+            || !at_prompt(terminal, meta.live_pid())
+        {
+            continue;
+        }
+        log::debug!("hibernating {:?}", meta.argv);
+        meta.hibernating = true;
+        meta.search_matches = Vec::new();
+        meta.search_current = None;
+        meta.cpu_sample = None;
+        meta.busy = None;
+        meta.timestamps.shrink_to_fit();
+    }
+}
+
+// on output, and for every pane of a page that's switched to
+pub fn wake(term: &Term, terminal: &vte::Terminal) {
+    let imp = TermImpl::from_instance(term);
+    if let Some(meta) = imp.tabs.borrow_mut().by_terminal_mut(terminal) {
+        meta.last_output = Instant::now();
+        if meta.hibernating {
+            log::debug!("waking {:?}", meta.argv);
+            meta.hibernating = false;
+        }
+    }
+}
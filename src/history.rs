@@ -0,0 +1,196 @@
+// every command line shell integration reports, from all tabs and windows,
+// kept in ~/.local/share/pterm/history for the "Command History" picker. Like
+// the shells' own HISTCONTROL=ignorespace, lines starting with a space aren't
+// kept.
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use gtk::prelude::*;
+use vte::TerminalExt;
+
+use crate::{picker, Term};
+
+const FILE: &str = "history";
+// the file is cut back to this many lines when it's twice as long
+const MAX_ENTRIES: usize = 10_000;
+// rows in the picker, newest first
+const SHOWN: usize = 2_000;
+
+struct Entry {
+    cwd: Option<PathBuf>,
+    command: String,
+}
+
+// one per line: unix time, cwd and command, tab separated
+pub fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+}
+
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => unescaped.push('\t'),
+            Some('n') => unescaped.push('\n'),
+            Some(c) => unescaped.push(c),
+            None => {}
+        }
+    }
+    unescaped
+}
+
+fn parse(line: &str) -> Option<Entry> {
+    let mut fields = line.splitn(3, '\t');
+    let _time = fields.next()?;
+    let cwd = fields.next()?;
+    let command = unescape(fields.next()?);
+    Some(Entry {
+        cwd: Some(PathBuf::from(unescape(cwd))).filter(|_| !cwd.is_empty()),
+        command,
+    })
+}
+
+fn path() -> Option<PathBuf> {
+    xdg::BaseDirectories::with_prefix("pterm")
+        .ok()?
+        .place_data_file(FILE)
+        .ok()
+}
+
+pub fn record(command: &str, cwd: Option<&Path>) {
+    if command.trim().is_empty() || command.starts_with(' ') {
+        return;
+    }
+    let path = match path() {
+        Some(path) => path,
+        None => return,
+    };
+    let time = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|time| time.as_secs())
+        .unwrap_or_default();
+    let line = format!(
+        "{}\t{}\t{}\n",
+        time,
+        cwd.map(|cwd| escape(&cwd.display().to_string()))
+            .unwrap_or_default(),
+        escape(command.trim_end())
+    );
+    let mut options = std::fs::OpenOptions::new();
+    options.create(true).append(true);
+    // command lines have passwords and tokens in them
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let written = options
+        .open(&path)
+        .and_then(|mut file| file.write_all(line.as_bytes()));
+    if let Err(err) = written {
+        log::warn!("can't write {}: {}", path.display(), err);
+    }
+}
+
+// newest first, each command once
+fn load() -> Vec<Entry> {
+    let path = match path() {
+        Some(path) => path,
+        None => return Vec::new(),
+    };
+    let text = match std::fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(_) => return Vec::new(),
+    };
+    let lines: Vec<&str> = text.lines().collect();
+    if lines.len() > MAX_ENTRIES * 2 {
+        let mut kept = lines[lines.len() - MAX_ENTRIES..].join("\n");
+        kept.push('\n');
+        if let Err(err) = std::fs::write(&path, kept) {
+            log::warn!("can't write {}: {}", path.display(), err);
+        }
+    }
+    let mut seen = std::collections::HashSet::new();
+    lines
+        .iter()
+        .rev()
+        .filter_map(|line| parse(line))
+        .filter(|entry| seen.insert(entry.command.clone()))
+        .take(SHOWN)
+        .collect()
+}
+
+// the picked command is typed at the prompt of the active terminal, not run
+pub fn show(term: &Term) {
+    let terminal = match term.active_terminal() {
+        Some(terminal) => terminal,
+        None => return,
+    };
+    let entries = load();
+    if entries.is_empty() {
+        term.show_warning("no commands yet, command history needs shell integration");
+        return;
+    }
+    let items = entries
+        .iter()
+        .map(|entry| picker::Item {
+            label: entry.command.replace('\n', " "),
+            detail: entry.cwd.as_ref().map(|cwd| cwd.display().to_string()),
+        })
+        .collect();
+    picker::show(term, "Command History", items, move |index| {
+        terminal.feed_child(entries[index].command.as_bytes());
+        terminal.grab_focus();
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escape_keeps_one_entry_per_line() {
+        assert_eq!(escape("ls -l"), "ls -l");
+        assert_eq!(escape("a\tb\nc"), "a\\tb\\nc");
+        assert_eq!(escape("echo \\n"), "echo \\\\n");
+        assert!(!escape("for x in 1 2\ndo\techo $x\ndone").contains(['\t', '\n']));
+    }
+
+    #[test]
+    fn unescape_undoes_escape() {
+        for text in [
+            "",
+            "ls -l",
+            "printf 'a\\tb\\n'",
+            "for x in 1 2\ndo\techo $x\ndone",
+            "trailing \\",
+            "\\\\\\t\t\n",
+        ] {
+            assert_eq!(unescape(&escape(text)), text);
+        }
+    }
+
+    #[test]
+    fn unescape_takes_unknown_escapes_literally() {
+        assert_eq!(unescape("a\\qb"), "aqb");
+        assert_eq!(unescape("end\\"), "end");
+    }
+
+    #[test]
+    fn parse_entries() {
+        let entry = parse("1700000000\t/home/me/my\\tdir\tgit commit -m 'a\\nb'").unwrap();
+        assert_eq!(entry.cwd, Some(PathBuf::from("/home/me/my\tdir")));
+        assert_eq!(entry.command, "git commit -m 'a\nb'");
+
+        let entry = parse("1700000000\t\tls").unwrap();
+        assert_eq!(entry.cwd, None);
+        assert_eq!(entry.command, "ls");
+
+        assert!(parse("1700000000\t/tmp").is_none());
+    }
+}
@@ -0,0 +1,257 @@
+// [[keys]] from the config: key combinations that type something into the
+// terminal, for snippets and for keys a remote program reads differently
+
+use gtk::prelude::*;
+use vte::TerminalExt;
+
+use crate::config::ApplicationMode;
+use crate::Term;
+
+#[derive(Debug, Clone)]
+pub struct KeyBinding {
+    key: gdk::keys::Key,
+    modifiers: gdk::ModifierType,
+    bytes: Vec<u8>,
+}
+
+// "<Ctrl><Alt>g" as gtk writes accelerators
+pub fn parse_key(accelerator: &str) -> Option<(gdk::keys::Key, gdk::ModifierType)> {
+    let (keyval, modifiers) = gtk::accelerator_parse(accelerator);
+    if keyval == 0 {
+        return None;
+    }
+    Some((gdk::keys::Key::from(keyval).to_lower(), modifiers))
+}
+
+// \e, \xNN, \n, \r, \t, \a and \\, the rest is taken as it is
+pub fn unescape(text: &str) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            let mut buf = [0; 4];
+            bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+            continue;
+        }
+        match chars.next() {
+            Some('e') => bytes.push(0x1b),
+            Some('n') => bytes.push(b'\n'),
+            Some('r') => bytes.push(b'\r'),
+            Some('t') => bytes.push(b'\t'),
+            Some('a') => bytes.push(0x07),
+            Some('\\') => bytes.push(b'\\'),
+            Some('x') => {
+                let hex: String = chars.by_ref().take(2).collect();
+                match u8::from_str_radix(&hex, 16) {
+                    Ok(byte) if hex.len() == 2 => bytes.push(byte),
+                    _ => return Err(format!("invalid escape \\x{} in {:?}", hex, text)),
+                }
+            }
+            Some(other) => return Err(format!("unknown escape \\{} in {:?}", other, text)),
+            None => return Err(format!("{:?} ends with a backslash", text)),
+        }
+    }
+    Ok(bytes)
+}
+
+impl KeyBinding {
+    pub fn new(key: gdk::keys::Key, modifiers: gdk::ModifierType, bytes: Vec<u8>) -> Self {
+        KeyBinding {
+            key,
+            modifiers,
+            bytes,
+        }
+    }
+
+    fn matches(&self, event: &gdk::EventKey, physical: bool) -> bool {
+        let modifiers = event.state() & gtk::accelerator_get_default_mod_mask();
+        let key = match physical.then(|| position(event)).flatten() {
+            Some(key) => key,
+            None => event.keyval().to_lower(),
+        };
+        key == self.key && modifiers == self.modifiers
+    }
+}
+
+// use_physical_keys: the key in the place of `event`'s on a US keyboard. X
+// and wayland keycodes are evdev's plus 8 whatever the layout, so it's the
+// same key on dvorak or a cyrillic layout; only letters, digits and the
+// punctuation between them, the rest has the same keysym everywhere
+#[cfg(not(any(windows, target_os = "macos")))]
+pub fn position(event: &gdk::EventKey) -> Option<gdk::keys::Key> {
+    const ROWS: [(u16, &str); 4] = [
+        (2 + 8, "1234567890-="),
+        (16 + 8, "qwertyuiop[]"),
+        (30 + 8, "asdfghjkl;'"),
+        (44 + 8, "zxcvbnm,./"),
+    ];
+    let code = event.hardware_keycode();
+    let c = ROWS.iter().find_map(|(first, keys)| {
+        let index = code.checked_sub(*first)?;
+        keys.chars().nth(index as usize)
+    })?;
+    Some(gdk::keys::Key::from(gdk::unicode_to_keyval(c as u32)))
+}
+
+// keycodes mean something else there
+#[cfg(any(windows, target_os = "macos"))]
+pub fn position(_event: &gdk::EventKey) -> Option<gdk::keys::Key> {
+    None
+}
+
+// the key `event` is for, as it would be with `state` instead of its own
+// modifiers
+pub fn translate(event: &gdk::EventKey, state: gdk::ModifierType) -> Option<gdk::keys::Key> {
+    let display = gdk::Display::default()?;
+    let keymap = gdk::Keymap::for_display(&display)?;
+    let (keyval, _, _, _) = keymap.translate_keyboard_state(
+        event.hardware_keycode() as u32,
+        state,
+        event.group() as i32,
+    )?;
+    Some(gdk::keys::Key::from(keyval))
+}
+
+// application_cursor and application_keypad other than "auto": the keys are
+// sent here as the mode says rather than by vte as the program asked
+pub fn force_application_mode(
+    terminal: &vte::Terminal,
+    event: &gdk::EventKey,
+    cursor: ApplicationMode,
+    keypad: ApplicationMode,
+) -> bool {
+    use gdk::keys::constants as key;
+
+    let modifiers = event.state() & gtk::accelerator_get_default_mod_mask();
+    if !modifiers.is_empty() {
+        return false;
+    }
+    let cursor_key = match event.keyval() {
+        key::Up => Some('A'),
+        key::Down => Some('B'),
+        key::Right => Some('C'),
+        key::Left => Some('D'),
+        key::Home => Some('H'),
+        key::End => Some('F'),
+        _ => None,
+    };
+    if let Some(c) = cursor_key {
+        let bytes = match cursor {
+            ApplicationMode::Auto => return false,
+            ApplicationMode::On => format!("\x1bO{}", c),
+            ApplicationMode::Off => format!("\x1b[{}", c),
+        };
+        terminal.feed_child(bytes.as_bytes());
+        return true;
+    }
+    // what the key types, and its final byte in application mode
+    let (typed, application) = match event.keyval() {
+        key::KP_0 => ("0", 'p'),
+        key::KP_1 => ("1", 'q'),
+        key::KP_2 => ("2", 'r'),
+        key::KP_3 => ("3", 's'),
+        key::KP_4 => ("4", 't'),
+        key::KP_5 => ("5", 'u'),
+        key::KP_6 => ("6", 'v'),
+        key::KP_7 => ("7", 'w'),
+        key::KP_8 => ("8", 'x'),
+        key::KP_9 => ("9", 'y'),
+        key::KP_Decimal => (".", 'n'),
+        key::KP_Add => ("+", 'k'),
+        key::KP_Subtract => ("-", 'm'),
+        key::KP_Multiply => ("*", 'j'),
+        key::KP_Divide => ("/", 'o'),
+        key::KP_Enter => ("\r", 'M'),
+        _ => return false,
+    };
+    let bytes = match keypad {
+        ApplicationMode::Auto => return false,
+        ApplicationMode::On => format!("\x1bO{}", application),
+        ApplicationMode::Off => typed.to_string(),
+    };
+    terminal.feed_child(bytes.as_bytes());
+    true
+}
+
+// ctrl_c_copies: Ctrl+C copies the selection, if there's one, and Ctrl+V
+// pastes, as in editors; without a selection Ctrl+C goes to the terminal
+// and interrupts as usual
+pub fn editor_clipboard(terminal: &vte::Terminal, event: &gdk::EventKey) -> bool {
+    let modifiers = event.state() & gtk::accelerator_get_default_mod_mask();
+    if modifiers != gdk::ModifierType::CONTROL_MASK {
+        return false;
+    }
+    match event.keyval().to_lower() {
+        gdk::keys::constants::c if terminal.has_selection() => {
+            terminal.copy_clipboard_format(vte::Format::Text);
+            terminal.unselect_all();
+            true
+        }
+        gdk::keys::constants::v => {
+            terminal.paste_clipboard();
+            true
+        }
+        _ => false,
+    }
+}
+
+// the digit on the key, for Alt+digit: AZERTY and others have the digits on
+// the shifted level, so it's looked up in the keymap when the key typed
+// something else
+pub fn digit(event: &gdk::EventKey) -> Option<u32> {
+    let digit = |key: gdk::keys::Key| key.to_unicode().and_then(|c| c.to_digit(10));
+    digit(event.keyval())
+        .or_else(|| translate(event, gdk::ModifierType::empty()).and_then(digit))
+        .or_else(|| translate(event, gdk::ModifierType::SHIFT_MASK).and_then(digit))
+}
+
+// alt_sends_escape = false: Alt+key types a character rather than Esc and the
+// key, the one on the AltGr level if the layout has one there; keys without
+// a character, and Ctrl+Alt, are left to vte
+pub fn compose_alt(terminal: &vte::Terminal, event: &gdk::EventKey) -> bool {
+    let state = event.state();
+    if !state.contains(gdk::ModifierType::MOD1_MASK)
+        || state.contains(gdk::ModifierType::CONTROL_MASK)
+    {
+        return false;
+    }
+    let plain = state - gdk::ModifierType::MOD1_MASK;
+    let composed = translate(event, plain | gdk::ModifierType::MOD5_MASK)
+        .and_then(|key| key.to_unicode())
+        .or_else(|| translate(event, plain).and_then(|key| key.to_unicode()));
+    match composed {
+        Some(c) if !c.is_control() => {
+            let mut buf = [0; 4];
+            terminal.feed_child(c.encode_utf8(&mut buf).as_bytes());
+            true
+        }
+        _ => false,
+    }
+}
+
+// true if the key was bound and its text sent to the focused terminal
+pub fn handle_key(
+    term: &Term,
+    bindings: &[KeyBinding],
+    event: &gdk::EventKey,
+    physical: bool,
+) -> bool {
+    // not while typing into the find bar or a dialog
+    let terminal = match term
+        .focus()
+        .and_then(|focus| focus.downcast::<vte::Terminal>().ok())
+    {
+        Some(terminal) => terminal,
+        None => return false,
+    };
+    match bindings
+        .iter()
+        .find(|binding| binding.matches(event, physical))
+    {
+        Some(binding) => {
+            terminal.feed_child(&binding.bytes);
+            true
+        }
+        None => false,
+    }
+}
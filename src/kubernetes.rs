@@ -0,0 +1,203 @@
+// "Kubernetes Shell…": a context, a namespace and a running pod are picked in
+// turn, each step skipped when there's only one, and the pod gets a tab with
+// `kubectl exec -it` in it. The tab is colored as [kubernetes] says so it's not
+// mistaken for a local one.
+
+use std::rc::Rc;
+
+use gtk::prelude::*;
+use gtk::subclass::prelude::*;
+
+use crate::{picker, tabs, Term, TermImpl};
+
+#[derive(Debug, Clone)]
+pub struct Settings {
+    pub kubectl: String,
+    pub shell: String,
+    pub contexts: Vec<String>,
+    pub colors: tabs::Colors,
+}
+
+// kubectl's output lines, with `kind` ("pod/" and the like) taken off
+fn kubectl(
+    term: &Term,
+    settings: &Settings,
+    args: &[&str],
+    kind: &'static str,
+    done: impl FnOnce(&Term, Vec<String>) + 'static,
+) {
+    let argv: Vec<&std::ffi::OsStr> = std::iter::once(settings.kubectl.as_str())
+        .chain(args.iter().copied())
+        .map(std::ffi::OsStr::new)
+        .collect();
+    let flags = gio::SubprocessFlags::STDOUT_PIPE | gio::SubprocessFlags::STDERR_PIPE;
+    let process = match gio::Subprocess::newv(&argv, flags) {
+        Ok(process) => process,
+        Err(err) => {
+            term.show_warning(&format!("can't run {}: {}", settings.kubectl, err));
+            return;
+        }
+    };
+    let name = settings.kubectl.clone();
+    process.communicate_utf8_async(
+        None,
+        None::<&gio::Cancellable>,
+        glib::clone!(@weak term, @strong process => move |result| {
+            let (stdout, stderr) = match result {
+                Ok(output) => output,
+                Err(err) => {
+                    term.show_warning(&format!("{} failed: {}", name, err));
+                    return;
+                }
+            };
+            if !process.is_successful() {
+                let stderr = stderr.map(|stderr| stderr.trim().to_string()).unwrap_or_default();
+                term.show_warning(&format!("{} failed: {}", name, stderr));
+                return;
+            }
+            let lines = stdout
+                .as_deref()
+                .unwrap_or_default()
+                .lines()
+                .map(|line| line.trim())
+                .map(|line| line.strip_prefix(kind).unwrap_or(line).to_string())
+                .filter(|line| !line.is_empty())
+                .collect();
+            done(&term, lines);
+        }),
+    );
+}
+
+// straight on with the only one there is
+fn choose(
+    term: &Term,
+    title: &str,
+    what: &str,
+    names: Vec<String>,
+    next: impl Fn(&Term, &str) + 'static,
+) {
+    match names.len() {
+        0 => term.show_warning(&format!("no {} to pick from", what)),
+        1 => next(term, &names[0]),
+        _ => {
+            let items = names
+                .iter()
+                .map(|name| picker::Item {
+                    label: name.clone(),
+                    detail: None,
+                })
+                .collect();
+            picker::show(
+                term,
+                title,
+                items,
+                glib::clone!(@weak term => move |index| next(&term, &names[index])),
+            );
+        }
+    }
+}
+
+pub fn show(term: &Term) {
+    let settings = TermImpl::from_instance(term)
+        .config
+        .borrow()
+        .kubernetes
+        .clone();
+    let settings = match settings {
+        Some(settings) => Rc::new(settings),
+        None => {
+            term.show_warning("add a [kubernetes] section to the config to open pod shells");
+            return;
+        }
+    };
+    let pick_context = glib::clone!(@strong settings => move |term: &Term, contexts| {
+        let settings = settings.clone();
+        choose(term, "Kubernetes Context", "contexts", contexts, move |term, context| {
+            namespaces(term, &settings, context);
+        });
+    });
+    if settings.contexts.is_empty() {
+        let args = ["config", "get-contexts", "-o", "name"];
+        kubectl(term, &settings, &args, "", pick_context);
+    } else {
+        pick_context(term, settings.contexts.clone());
+    }
+}
+
+fn namespaces(term: &Term, settings: &Rc<Settings>, context: &str) {
+    let context = context.to_string();
+    let args = [
+        "--context",
+        context.as_str(),
+        "get",
+        "namespaces",
+        "-o",
+        "name",
+    ];
+    kubectl(
+        term,
+        settings,
+        &args,
+        "namespace/",
+        glib::clone!(@strong settings, @strong context => move |term, namespaces| {
+            choose(term, "Kubernetes Namespace", "namespaces", namespaces, move |term, namespace| {
+                pods(term, &settings, &context, namespace);
+            });
+        }),
+    );
+}
+
+fn pods(term: &Term, settings: &Rc<Settings>, context: &str, namespace: &str) {
+    let (context, namespace) = (context.to_string(), namespace.to_string());
+    let args = [
+        "--context",
+        context.as_str(),
+        "--namespace",
+        namespace.as_str(),
+        "get",
+        "pods",
+        "--field-selector=status.phase=Running",
+        "-o",
+        "name",
+    ];
+    kubectl(
+        term,
+        settings,
+        &args,
+        "pod/",
+        glib::clone!(@strong settings, @strong context, @strong namespace => move |term, pods| {
+            choose(term, "Kubernetes Pod", "running pods", pods, move |term, pod| {
+                open(term, &settings, &context, &namespace, pod);
+            });
+        }),
+    );
+}
+
+fn open(term: &Term, settings: &Settings, context: &str, namespace: &str, pod: &str) {
+    let argv: Vec<String> = [
+        settings.kubectl.as_str(),
+        "--context",
+        context,
+        "--namespace",
+        namespace,
+        "exec",
+        "-it",
+        pod,
+        "--",
+        settings.shell.as_str(),
+    ]
+    .iter()
+    .map(|arg| arg.to_string())
+    .collect();
+    let terminal = term.add_new_tab_with_command(Some(&argv));
+    let imp = TermImpl::from_instance(term);
+    if let Some(meta) = imp.tabs.borrow_mut().by_terminal_mut(&terminal) {
+        meta.title = Some(format!("{}/{} ({})", namespace, pod, context));
+        if !settings.colors.is_empty() {
+            meta.colors = Some(settings.colors);
+        }
+    }
+    term.apply_colors(&terminal);
+    term.refresh_label(&terminal);
+    terminal.grab_focus();
+}
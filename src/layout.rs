@@ -0,0 +1,250 @@
+// named layouts: the tabs and splits of a window with their commands and
+// working directories, kept as toml under $XDG_DATA_HOME/pterm/layouts
+
+use std::path::PathBuf;
+
+use anyhow::Error;
+use gtk::prelude::*;
+use gtk::subclass::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{split, Term, TermImpl};
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Layout {
+    #[serde(default)]
+    pub tabs: Vec<Tab>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Tab {
+    #[serde(default)]
+    pub pinned: bool,
+    pub root: Node,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Orientation {
+    Horizontal,
+    Vertical,
+}
+
+impl From<Orientation> for gtk::Orientation {
+    fn from(orientation: Orientation) -> Self {
+        match orientation {
+            Orientation::Horizontal => gtk::Orientation::Horizontal,
+            Orientation::Vertical => gtk::Orientation::Vertical,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Node {
+    Terminal {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        title: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        cwd: Option<PathBuf>,
+        // the default shell when empty
+        #[serde(default)]
+        command: Vec<String>,
+    },
+    Split {
+        orientation: Orientation,
+        // pixels from the left or top edge
+        position: i32,
+        first: Box<Node>,
+        second: Box<Node>,
+    },
+}
+
+fn check_name(name: &str) -> Result<(), Error> {
+    if name.is_empty() || name.starts_with('.') || name.contains(std::path::is_separator) {
+        anyhow::bail!("layout names can't be empty, start with a dot or contain slashes");
+    }
+    Ok(())
+}
+
+fn file_name(name: &str) -> String {
+    format!("layouts/{}.toml", name)
+}
+
+pub fn saved_names() -> Vec<String> {
+    let dirs = match xdg::BaseDirectories::with_prefix("pterm") {
+        Ok(dirs) => dirs,
+        Err(_) => return Vec::new(),
+    };
+    let mut names: Vec<String> = dirs
+        .list_data_files("layouts")
+        .into_iter()
+        .filter(|path| path.extension().map(|ext| ext == "toml").unwrap_or(false))
+        .filter_map(|path| Some(path.file_stem()?.to_str()?.to_string()))
+        .collect();
+    names.sort();
+    names.dedup();
+    names
+}
+
+fn capture(term: &Term, widget: &gtk::Widget) -> Option<Node> {
+    if let Some(terminal) = widget.downcast_ref::<vte::Terminal>() {
+        let imp = TermImpl::from_instance(term);
+        let tabs = imp.tabs.borrow();
+        let meta = tabs.by_terminal(terminal)?;
+        // tmux keeps those itself, and there's no command to run them again
+        if meta.tmux.is_some() {
+            return None;
+        }
+        return Some(Node::Terminal {
+            title: meta.title.clone(),
+            cwd: term.get_terminal_cwd(terminal).or_else(|| meta.cwd.clone()),
+            command: meta.argv.clone(),
+        });
+    }
+    if let Some(paned) = widget.downcast_ref::<gtk::Paned>() {
+        let first = paned.child1().and_then(|child| capture(term, &child));
+        let second = paned.child2().and_then(|child| capture(term, &child));
+        return match (first, second) {
+            (Some(first), Some(second)) => Some(Node::Split {
+                orientation: match paned.orientation() {
+                    gtk::Orientation::Horizontal => Orientation::Horizontal,
+                    _ => Orientation::Vertical,
+                },
+                position: paned.position(),
+                first: Box::new(first),
+                second: Box::new(second),
+            }),
+            (first, second) => first.or(second),
+        };
+    }
+    // the page box
+    let page = widget.downcast_ref::<gtk::Container>()?;
+    capture(term, page.children().first()?)
+}
+
+// `panes` collects the terminals in tree order
+fn build(term: &Term, node: &Node, panes: &mut Vec<vte::Terminal>) -> gtk::Widget {
+    match node {
+        Node::Terminal {
+            title,
+            cwd,
+            command,
+        } => {
+            let command = Some(command.as_slice()).filter(|command| !command.is_empty());
+            let terminal = term.new_pane_with(command, cwd.clone(), Vec::new());
+            let imp = TermImpl::from_instance(term);
+            if let Some(meta) = imp.tabs.borrow_mut().by_terminal_mut(&terminal) {
+                meta.title = title.clone();
+            }
+            panes.push(terminal.clone());
+            terminal.upcast()
+        }
+        Node::Split {
+            orientation,
+            position,
+            first,
+            second,
+        } => {
+            let paned = gtk::Paned::new((*orientation).into());
+            paned.pack1(&build(term, first, panes), true, false);
+            paned.pack2(&build(term, second, panes), true, false);
+            paned.set_position(*position);
+            paned.upcast()
+        }
+    }
+}
+
+// the tabs and splits of the window as they are now
+pub fn snapshot(term: &Term) -> Layout {
+    let tabs = term
+        .notebook()
+        .children()
+        .iter()
+        .filter_map(|page| {
+            Some(Tab {
+                pinned: term.is_pinned(page),
+                root: capture(term, page)?,
+            })
+        })
+        .collect();
+    Layout { tabs }
+}
+
+pub fn save(term: &Term, name: &str) -> Result<(), Error> {
+    check_name(name)?;
+    let layout = snapshot(term);
+
+    let dirs = xdg::BaseDirectories::with_prefix("pterm")?;
+    let path = dirs.place_data_file(file_name(name))?;
+    std::fs::write(&path, toml::to_string(&layout)?)?;
+    log::info!("saved layout {:?} to {}", name, path.display());
+    Ok(())
+}
+
+pub fn load(term: &Term, name: &str) -> Result<(), Error> {
+    check_name(name)?;
+    let dirs = xdg::BaseDirectories::with_prefix("pterm")?;
+    let path = dirs
+        .find_data_file(file_name(name))
+        .ok_or_else(|| anyhow::anyhow!("no such layout"))?;
+    let layout: Layout = toml::from_slice(&std::fs::read(&path)?)?;
+    restore(term, &layout);
+    Ok(())
+}
+
+// the tabs of the layout are added after the ones already open
+pub fn restore(term: &Term, layout: &Layout) {
+    let imp = TermImpl::from_instance(term);
+    for tab in &layout.tabs {
+        let mut panes = Vec::new();
+        let root = build(term, &tab.root, &mut panes);
+        for terminal in &panes {
+            if let Some(meta) = imp.tabs.borrow_mut().by_terminal_mut(terminal) {
+                meta.pinned = tab.pinned;
+            }
+        }
+        if let Some(first) = panes.first() {
+            term.insert_tab(&split::new_page(&root), first);
+        }
+    }
+    term.refresh_labels();
+}
+
+// asks for a layout name and runs `action` ("save_layout" or "load_layout") with it
+pub fn prompt(term: &Term, title: &str, action: &'static str) {
+    let dialog = gtk::Dialog::with_buttons(
+        Some(title),
+        Some(term),
+        gtk::DialogFlags::MODAL | gtk::DialogFlags::DESTROY_WITH_PARENT,
+        &[
+            ("Cancel", gtk::ResponseType::Cancel),
+            ("OK", gtk::ResponseType::Ok),
+        ],
+    );
+    dialog.set_default_response(gtk::ResponseType::Ok);
+
+    let names = gtk::ComboBoxText::with_entry();
+    for name in saved_names() {
+        names.append_text(&name);
+    }
+    if let Some(entry) = names
+        .child()
+        .and_then(|child| child.downcast::<gtk::Entry>().ok())
+    {
+        entry.set_activates_default(true);
+    }
+    dialog.content_area().pack_start(&names, false, false, 6);
+
+    dialog.connect_response(
+        glib::clone!(@weak term, @weak names => move |dialog, response| {
+            if response == gtk::ResponseType::Ok {
+                if let Some(name) = names.active_text() {
+                    term.activate_action(action, Some(&name.to_variant()));
+                }
+            }
+            dialog.close();
+        }),
+    );
+    dialog.show_all();
+}
@@ -0,0 +1,185 @@
+// urls, paths, email addresses and [[matches]] under the pointer: vte
+// underlines them and [[clicks]] says what each gesture on them does
+
+use std::path::PathBuf;
+
+use gtk::prelude::*;
+use gtk::subclass::prelude::*;
+use vte::TerminalExt;
+
+use crate::config::{ClickAction, ClickConfig, Gesture, LinkActivation};
+use crate::{Term, TermImpl};
+
+// PCRE2_MULTILINE, which vte wants for match regexes
+const PCRE2_MULTILINE: u32 = 0x0000_0400;
+
+pub const URL: &str = "url";
+pub const FILE: &str = "file";
+pub const EMAIL: &str = "email";
+
+const BUILTIN: [(&str, &str); 3] = [
+    (
+        URL,
+        r#"\b(?:https?|ftp|file)://[^\s<>"'`]*[^\s<>"'`.,;:!?)\]]"#,
+    ),
+    // absolute, home or ./relative paths, not the : and line number after them
+    (
+        FILE,
+        r#"(?<![\w/.~])(?:~|\.\.?)?/[^\s:<>"'`]*[^\s:<>"'`.,;)\]]"#,
+    ),
+    (EMAIL, r"\b[\w.+-]+@[\w-]+(?:\.[\w-]+)+\b"),
+];
+
+#[derive(Debug, Clone)]
+pub struct Pattern {
+    pub name: String,
+    pub regex: vte::Regex,
+}
+
+pub fn compile(name: &str, regex: &str) -> Result<Pattern, glib::Error> {
+    Ok(Pattern {
+        name: name.to_string(),
+        regex: vte::Regex::for_match(regex, PCRE2_MULTILINE)?,
+    })
+}
+
+pub fn builtin() -> Vec<Pattern> {
+    BUILTIN
+        .iter()
+        .map(|(name, regex)| compile(name, regex).expect("builtin match regex"))
+        .collect()
+}
+
+// what's used when [[clicks]] has nothing for a gesture
+pub fn default_clicks(links: LinkActivation) -> Vec<ClickConfig> {
+    let url = match links {
+        LinkActivation::Click => Some(Gesture::Click),
+        LinkActivation::CtrlClick => Some(Gesture::CtrlClick),
+        LinkActivation::Never => None,
+    };
+    let defaults = [
+        (URL, url),
+        (FILE, Some(Gesture::CtrlClick)),
+        (EMAIL, Some(Gesture::CtrlClick)),
+    ];
+    defaults
+        .iter()
+        .filter_map(|(class, gesture)| {
+            Some(ClickConfig {
+                class: class.to_string(),
+                gesture: (*gesture)?,
+                action: ClickAction::Open,
+            })
+        })
+        .collect()
+}
+
+// link_activation = "never" is never overridden by [[clicks]]
+pub fn allowed(click: &ClickConfig, links: LinkActivation) -> bool {
+    !(links == LinkActivation::Never && click.class == URL && click.action == ClickAction::Open)
+}
+
+// called from apply_config, the tags are kept to tell the matches apart
+pub fn add_matches(term: &Term, terminal: &vte::Terminal) {
+    let imp = TermImpl::from_instance(term);
+    terminal.match_remove_all();
+    let tags: Vec<(i32, String)> = imp
+        .config
+        .borrow()
+        .matches
+        .iter()
+        .map(|pattern| {
+            let tag = terminal.match_add_regex(&pattern.regex, 0);
+            terminal.match_set_cursor_name(tag, "pointer");
+            (tag, pattern.name.clone())
+        })
+        .collect();
+    if let Some(meta) = imp.tabs.borrow_mut().by_terminal_mut(terminal) {
+        meta.match_tags = tags;
+    }
+}
+
+fn gesture(event: &gdk::EventButton) -> Option<Gesture> {
+    let ctrl = event.state().contains(gdk::ModifierType::CONTROL_MASK);
+    let double = event.event_type() == gdk::EventType::DoubleButtonPress;
+    match event.button() {
+        gdk::BUTTON_PRIMARY if double => Some(Gesture::DoubleClick),
+        gdk::BUTTON_PRIMARY if ctrl => Some(Gesture::CtrlClick),
+        gdk::BUTTON_PRIMARY => Some(Gesture::Click),
+        gdk::BUTTON_MIDDLE => Some(Gesture::MiddleClick),
+        gdk::BUTTON_SECONDARY => Some(Gesture::RightClick),
+        _ => None,
+    }
+}
+
+// from the button-press-event handler, true if the click was used
+pub fn handle_click(term: &Term, terminal: &vte::Terminal, event: &gdk::EventButton) -> bool {
+    let imp = TermImpl::from_instance(term);
+    let gesture = match gesture(event) {
+        Some(gesture) => gesture,
+        None => return false,
+    };
+    let event: &gdk::Event = event;
+    let (text, tag) = terminal.match_check_event(event);
+    let text = match text {
+        Some(text) => text.to_string(),
+        None => return false,
+    };
+    let class = match imp.tabs.borrow().by_terminal(terminal).and_then(|meta| {
+        meta.match_tags
+            .iter()
+            .find(|(known, _)| *known == tag)
+            .map(|(_, class)| class.clone())
+    }) {
+        Some(class) => class,
+        None => return false,
+    };
+    let action = imp
+        .config
+        .borrow()
+        .clicks
+        .iter()
+        .find(|click| click.class == class && click.gesture == gesture)
+        .map(|click| click.action);
+    match action {
+        Some(action) => {
+            run(term, terminal, &class, &text, action);
+            true
+        }
+        None => false,
+    }
+}
+
+fn run(term: &Term, terminal: &vte::Terminal, class: &str, text: &str, action: ClickAction) {
+    match action {
+        ClickAction::Open => {
+            let uri = match uri(term, terminal, class, text) {
+                Some(uri) => uri,
+                None => return,
+            };
+            if let Err(err) = gtk::show_uri_on_window(Some(term), &uri, gtk::current_event_time()) {
+                log::error!("failed to open {}: {}", uri, err);
+            }
+        }
+        ClickAction::Copy => gtk::Clipboard::get(&gdk::SELECTION_CLIPBOARD).set_text(text),
+        // typed at the prompt, without running it
+        ClickAction::Paste => terminal.feed_child(text.as_bytes()),
+    }
+}
+
+// paths are relative to where the terminal is, custom matches are opened as
+// they are and have to be uris
+fn uri(term: &Term, terminal: &vte::Terminal, class: &str, text: &str) -> Option<String> {
+    match class {
+        FILE => {
+            let path = match text.strip_prefix('~') {
+                Some(rest) => glib::home_dir().join(rest.trim_start_matches('/')),
+                None if text.starts_with('/') => PathBuf::from(text),
+                None => term.get_terminal_cwd(terminal)?.join(text),
+            };
+            Some(gio::File::for_path(path).uri().to_string())
+        }
+        EMAIL => Some(format!("mailto:{}", text)),
+        _ => Some(text.to_string()),
+    }
+}
@@ -0,0 +1,192 @@
+// locked tabs hide their terminals behind an unlock button, optionally
+// behind a command such as `pkexec true` that has to succeed; once locked a
+// tab locks itself again after lock_idle without typing
+
+use std::time::{Duration, Instant};
+
+use gtk::prelude::*;
+use gtk::subclass::prelude::*;
+
+use crate::{split, Term, TermImpl};
+
+const IDLE_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+pub fn is_locked(term: &Term, page: &gtk::Widget) -> bool {
+    let imp = TermImpl::from_instance(term);
+    imp.locked_pages
+        .borrow()
+        .iter()
+        .any(|(locked, _)| locked == page)
+}
+
+pub fn lock(term: &Term, page: &gtk::Widget) {
+    let imp = TermImpl::from_instance(term);
+    if is_locked(term, page) {
+        return;
+    }
+    let page_box = match page.downcast_ref::<gtk::Box>() {
+        Some(page_box) => page_box,
+        None => return,
+    };
+    let root = match page_box.children().into_iter().next() {
+        Some(root) => root,
+        None => return,
+    };
+    root.hide();
+
+    let icon = gtk::Image::from_icon_name(Some("changes-prevent-symbolic"), gtk::IconSize::Dialog);
+    let label = gtk::Label::new(Some("This tab is locked"));
+    let button = gtk::Button::with_label("Unlock");
+    let cover = gtk::Box::builder()
+        .orientation(gtk::Orientation::Vertical)
+        .spacing(12)
+        .halign(gtk::Align::Center)
+        .valign(gtk::Align::Center)
+        .build();
+    cover.pack_start(&icon, false, false, 0);
+    cover.pack_start(&label, false, false, 0);
+    cover.pack_start(&button, false, false, 0);
+    page_box.pack_start(&cover, true, true, 0);
+    cover.show_all();
+
+    button.connect_clicked(glib::clone!(@weak term, @weak page => move |button| {
+        button.set_sensitive(false);
+        authenticate(&term, &page, button);
+    }));
+    if term.active_page().as_ref() == Some(page) {
+        button.grab_focus();
+    }
+
+    imp.locked_pages
+        .borrow_mut()
+        .push((page.clone(), cover.upcast()));
+    for terminal in split::terminals(page) {
+        if let Some(meta) = imp.tabs.borrow_mut().by_terminal_mut(&terminal) {
+            meta.sensitive = true;
+        }
+    }
+    term.refresh_page_label(page);
+}
+
+// unlock_command has the last word if there is one
+fn authenticate(term: &Term, page: &gtk::Widget, button: &gtk::Button) {
+    let imp = TermImpl::from_instance(term);
+    let argv = imp.config.borrow().unlock_command.clone();
+    if argv.is_empty() {
+        unlock(term, page);
+        return;
+    }
+    let args: Vec<&std::ffi::OsStr> = argv.iter().map(|arg| arg.as_ref()).collect();
+    let process = match gio::Subprocess::newv(&args, gio::SubprocessFlags::NONE) {
+        Ok(process) => process,
+        Err(err) => {
+            log::error!("failed to run {:?}: {}", argv, err);
+            button.set_sensitive(true);
+            return;
+        }
+    };
+    process.wait_check_async(
+        None::<&gio::Cancellable>,
+        glib::clone!(@weak term, @weak page, @weak button => move |result| {
+            match result {
+                Ok(()) => unlock(&term, &page),
+                Err(err) => {
+                    log::info!("{:?} didn't unlock the tab: {}", argv, err);
+                    button.set_sensitive(true);
+                }
+            }
+        }),
+    );
+}
+
+pub fn unlock(term: &Term, page: &gtk::Widget) {
+    let imp = TermImpl::from_instance(term);
+    let cover = {
+        let mut locked = imp.locked_pages.borrow_mut();
+        match locked.iter().position(|(locked, _)| locked == page) {
+            Some(index) => locked.remove(index).1,
+            None => return,
+        }
+    };
+    cover.destroy();
+    if let Some(root) = page
+        .downcast_ref::<gtk::Box>()
+        .and_then(|page_box| page_box.children().into_iter().next())
+    {
+        root.show();
+    }
+    let now = Instant::now();
+    for terminal in split::terminals(page) {
+        if let Some(meta) = imp.tabs.borrow_mut().by_terminal_mut(&terminal) {
+            meta.last_input = now;
+        }
+    }
+    if let Some(terminal) = term.focused_terminal(page) {
+        terminal.grab_focus();
+    }
+    term.refresh_page_label(page);
+    watch_idle(term, page);
+}
+
+fn idle_for(term: &Term, page: &gtk::Widget) -> Duration {
+    let imp = TermImpl::from_instance(term);
+    let tabs = imp.tabs.borrow();
+    split::terminals(page)
+        .iter()
+        .filter_map(|terminal| tabs.by_terminal(terminal))
+        .map(|meta| meta.last_input.elapsed())
+        .min()
+        .unwrap_or_default()
+}
+
+// locks the page again after lock_idle without input, while it's open
+fn watch_idle(term: &Term, page: &gtk::Widget) {
+    if TermImpl::from_instance(term)
+        .config
+        .borrow()
+        .lock_idle
+        .is_none()
+    {
+        return;
+    }
+    glib::timeout_add_local(
+        IDLE_CHECK_INTERVAL,
+        glib::clone!(@weak term, @weak page => @default-return glib::Continue(false), move || {
+            if page.parent().is_none() || is_locked(&term, &page) {
+                return glib::Continue(false);
+            }
+            // read every time, a reload may have changed or dropped it
+            let idle = match TermImpl::from_instance(&term).config.borrow().lock_idle {
+                Some(idle) => idle,
+                None => return glib::Continue(false),
+            };
+            if idle_for(&term, &page) >= idle {
+                lock(&term, &page);
+                return glib::Continue(false);
+            }
+            glib::Continue(true)
+        }),
+    );
+}
+
+pub fn toggle(term: &Term) {
+    let page = match term.active_page() {
+        Some(page) => page,
+        None => return,
+    };
+    if is_locked(term, &page) {
+        let imp = TermImpl::from_instance(term);
+        let button = imp
+            .locked_pages
+            .borrow()
+            .iter()
+            .find(|(locked, _)| *locked == page)
+            .and_then(|(_, cover)| cover.downcast_ref::<gtk::Container>()?.children().pop())
+            .and_then(|button| button.downcast::<gtk::Button>().ok());
+        if let Some(button) = button {
+            button.clicked();
+        }
+    } else {
+        lock(term, &page);
+    }
+}
@@ -0,0 +1,187 @@
+// log records go to stderr as filtered by RUST_LOG, e.g.
+// RUST_LOG=pt::watch=debug for one subsystem; the last few are also kept,
+// at debug level for pterm itself, for the debug log window
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use gtk::prelude::*;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+const MAX_RECORDS: usize = 1000;
+
+struct Entry {
+    // counts every record ever kept, so the window knows what it has shown
+    serial: u64,
+    time: String,
+    level: Level,
+    target: String,
+    message: String,
+}
+
+struct Recent {
+    entries: VecDeque<Entry>,
+    next: u64,
+}
+
+struct Logger {
+    stderr: env_logger::Logger,
+}
+
+static RECENT: Mutex<Recent> = Mutex::new(Recent {
+    entries: VecDeque::new(),
+    next: 0,
+});
+
+fn keep(metadata: &Metadata) -> bool {
+    metadata.level() <= Level::Warn
+        || (metadata.level() <= Level::Debug && metadata.target().starts_with("pt"))
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.stderr.enabled(metadata) || keep(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if self.stderr.matches(record) {
+            self.stderr.log(record);
+        }
+        if !keep(record.metadata()) {
+            return;
+        }
+        let time = glib::DateTime::now_local()
+            .and_then(|now| now.format("%T"))
+            .map(|time| time.to_string())
+            .unwrap_or_default();
+        let mut recent = RECENT.lock().unwrap();
+        if recent.entries.len() >= MAX_RECORDS {
+            recent.entries.pop_front();
+        }
+        let serial = recent.next;
+        recent.next += 1;
+        recent.entries.push_back(Entry {
+            serial,
+            time,
+            level: record.level(),
+            target: record.target().to_string(),
+            message: record.args().to_string(),
+        });
+    }
+
+    fn flush(&self) {
+        self.stderr.flush();
+    }
+}
+
+pub fn init() {
+    let stderr = env_logger::Builder::from_default_env().build();
+    let max_level = stderr.filter().max(LevelFilter::Debug);
+    if log::set_boxed_logger(Box::new(Logger { stderr })).is_ok() {
+        log::set_max_level(max_level);
+    }
+}
+
+// lines kept since `serial`, as they're shown in the window
+fn since(serial: u64, target: Option<&str>) -> (Vec<String>, u64) {
+    let recent = RECENT.lock().unwrap();
+    let lines = recent
+        .entries
+        .iter()
+        .filter(|entry| entry.serial >= serial)
+        .filter(|entry| {
+            target
+                .map(|target| entry.target.starts_with(target))
+                .unwrap_or(true)
+        })
+        .map(|entry| {
+            format!(
+                "{} {:5} {}: {}\n",
+                entry.time, entry.level, entry.target, entry.message
+            )
+        })
+        .collect();
+    (lines, recent.next)
+}
+
+// subsystems that logged something so far, for the filter
+fn targets() -> Vec<String> {
+    let recent = RECENT.lock().unwrap();
+    let mut targets: Vec<String> = recent
+        .entries
+        .iter()
+        .map(|entry| entry.target.clone())
+        .collect();
+    targets.sort();
+    targets.dedup();
+    targets
+}
+
+// streams new records for as long as it's open
+pub fn show_window(parent: &impl IsA<gtk::Window>) {
+    let window = gtk::Window::builder()
+        .title("pterm debug log")
+        .transient_for(parent)
+        .default_width(800)
+        .default_height(400)
+        .build();
+
+    let filter = gtk::ComboBoxText::new();
+    filter.append(None, "All subsystems");
+    for target in targets() {
+        filter.append(Some(&target), &target);
+    }
+    filter.set_active(Some(0));
+
+    let text = gtk::TextView::builder()
+        .editable(false)
+        .monospace(true)
+        .cursor_visible(false)
+        .build();
+    let scrolled = gtk::ScrolledWindow::builder()
+        .child(&text)
+        .vexpand(true)
+        .build();
+
+    let content = gtk::Box::new(gtk::Orientation::Vertical, 6);
+    content.set_border_width(6);
+    content.pack_start(&filter, false, false, 0);
+    content.pack_start(&scrolled, true, true, 0);
+    window.add(&content);
+
+    let shown = std::rc::Rc::new(std::cell::Cell::new(0));
+    let update = glib::clone!(@weak text, @weak filter, @strong shown => move || {
+        let target = filter.active_id();
+        let (lines, next) = since(shown.get(), target.as_deref());
+        shown.set(next);
+        let buffer = text.buffer().unwrap();
+        let mut end = buffer.end_iter();
+        for line in lines {
+            buffer.insert(&mut end, &line);
+        }
+        text.scroll_to_iter(&mut buffer.end_iter(), 0.0, false, 0.0, 1.0);
+    });
+    update();
+
+    filter.connect_changed(
+        glib::clone!(@weak text, @strong shown, @strong update => move |_| {
+            text.buffer().unwrap().set_text("");
+            shown.set(0);
+            update();
+        }),
+    );
+    let source = glib::timeout_add_local(
+        std::time::Duration::from_millis(500),
+        glib::clone!(@strong update => move || {
+            update();
+            glib::Continue(true)
+        }),
+    );
+    let source = std::cell::Cell::new(Some(source));
+    window.connect_destroy(move |_| {
+        if let Some(source) = source.take() {
+            glib::source_remove(source);
+        }
+    });
+    window.show_all();
+}
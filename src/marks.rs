@@ -0,0 +1,379 @@
+// decorations vte doesn't know about, painted over the terminal after it drew
+
+use gtk::cairo;
+use gtk::prelude::*;
+use gtk::subclass::prelude::*;
+use regex::Regex;
+use vte::TerminalExt;
+
+use crate::tabs::Meta;
+use crate::{hacks, luminance, redact, search, select, shell, Term, TermImpl};
+
+const MAX_TIMESTAMPS: usize = 100_000;
+
+// from [[highlights]], applied to whatever is on screen
+#[derive(Debug, Clone)]
+pub struct HighlightRule {
+    pub regex: Regex,
+    pub color: gdk::RGBA,
+}
+
+// first visible row in the coordinates of cursor_position, and how many there are
+pub fn visible_rows(terminal: &vte::Terminal) -> (i64, i64) {
+    let top = terminal
+        .vadjustment()
+        .map(|adjustment| adjustment.value() as i64)
+        .unwrap_or(0);
+    (top, terminal.row_count() as i64)
+}
+
+// top of `row` in widget coordinates, None if it's scrolled out of view
+pub fn row_y(terminal: &vte::Terminal, row: i64) -> Option<f64> {
+    let (top, rows) = visible_rows(terminal);
+    if row < top || row >= top + rows {
+        return None;
+    }
+    let padding = terminal.style_context().padding(terminal.state_flags());
+    Some(padding.top as f64 + ((row - top) * terminal.char_height()) as f64)
+}
+
+// remembers when output first reached each row, called on contents-changed
+pub fn stamp_rows(term: &Term, terminal: &vte::Terminal) {
+    let imp = TermImpl::from_instance(term);
+    let (_, cursor_row) = terminal.cursor_position();
+    let cursor_row = cursor_row as i64;
+    if let Some(meta) = imp.tabs.borrow_mut().by_terminal_mut(terminal) {
+        let next = meta
+            .timestamps
+            .back()
+            .map(|(row, _)| row + 1)
+            .unwrap_or(cursor_row)
+            .max(cursor_row - terminal.row_count() as i64);
+        if next > cursor_row {
+            return;
+        }
+        let now = glib::real_time() / 1_000_000;
+        for row in next..=cursor_row {
+            if meta.timestamps.len() >= MAX_TIMESTAMPS {
+                meta.timestamps.pop_front();
+            }
+            meta.timestamps.push_back((row, now));
+        }
+    }
+}
+
+fn timestamp(meta: &Meta, row: i64) -> Option<i64> {
+    let index = meta
+        .timestamps
+        .binary_search_by_key(&row, |(row, _)| *row)
+        .ok()?;
+    Some(meta.timestamps[index].1)
+}
+
+// clock times along the right edge, over the text
+fn draw_timestamps(
+    ctx: &cairo::Context,
+    terminal: &vte::Terminal,
+    meta: &Meta,
+    (background, foreground): (gdk::RGBA, gdk::RGBA),
+) {
+    let (top, rows) = visible_rows(terminal);
+    ctx.select_font_face(
+        "monospace",
+        cairo::FontSlant::Normal,
+        cairo::FontWeight::Normal,
+    );
+    ctx.set_font_size(terminal.char_height() as f64 * 0.75);
+    let extents = match ctx.text_extents("00:00:00") {
+        Ok(extents) => extents,
+        Err(_) => return,
+    };
+    let width = extents.x_advance + 8.0;
+    let x = terminal.allocated_width() as f64 - width;
+
+    for row in top..top + rows {
+        let (y, time) = match (row_y(terminal, row), timestamp(meta, row)) {
+            (Some(y), Some(time)) => (y, time),
+            _ => continue,
+        };
+        let text =
+            match glib::DateTime::from_unix_local(time).and_then(|time| time.format("%H:%M:%S")) {
+                Some(text) => text,
+                None => continue,
+            };
+        ctx.set_source_rgba(background.red, background.green, background.blue, 0.85);
+        ctx.rectangle(x, y, width, terminal.char_height() as f64);
+        let _ = ctx.fill();
+        ctx.set_source_rgba(foreground.red, foreground.green, foreground.blue, 0.7);
+        ctx.move_to(x + 4.0, y + terminal.char_height() as f64 * 0.8);
+        let _ = ctx.show_text(&text);
+    }
+}
+
+// iTerm's badges: faint and large enough to tell tabs apart at a glance
+fn draw_badge(ctx: &cairo::Context, terminal: &vte::Terminal, text: &str, foreground: gdk::RGBA) {
+    if text.is_empty() {
+        return;
+    }
+    ctx.select_font_face(
+        "sans-serif",
+        cairo::FontSlant::Normal,
+        cairo::FontWeight::Bold,
+    );
+    ctx.set_font_size(terminal.char_height() as f64 * 2.0);
+    let extents = match ctx.text_extents(text) {
+        Ok(extents) => extents,
+        Err(_) => return,
+    };
+    let margin = terminal.char_width() as f64 * 2.0;
+    let x = terminal.allocated_width() as f64 - extents.x_advance - margin;
+    ctx.set_source_rgba(foreground.red, foreground.green, foreground.blue, 0.2);
+    ctx.move_to(x.max(margin), margin - extents.y_bearing);
+    let _ = ctx.show_text(text);
+}
+
+fn cursor_row(terminal: &vte::Terminal) -> i64 {
+    terminal.cursor_position().1 as i64
+}
+
+// the tab goes out of sight: whatever comes after the cursor is new
+pub fn set_seen_marker(term: &Term, terminal: &vte::Terminal) {
+    let imp = TermImpl::from_instance(term);
+    if let Some(meta) = imp.tabs.borrow_mut().by_terminal_mut(terminal) {
+        meta.seen_marker = Some(cursor_row(terminal));
+    }
+}
+
+// the marker only matters once something was written below it
+fn new_output_marker(terminal: &vte::Terminal, meta: &Meta) -> Option<i64> {
+    meta.seen_marker.filter(|&row| cursor_row(terminal) > row)
+}
+
+pub fn scroll_to_marker(term: &Term, terminal: &vte::Terminal) {
+    let imp = TermImpl::from_instance(term);
+    let row = match imp
+        .tabs
+        .borrow()
+        .by_terminal(terminal)
+        .and_then(|meta| new_output_marker(terminal, meta))
+    {
+        Some(row) => row,
+        None => return,
+    };
+    if let Some(adjustment) = terminal.vadjustment() {
+        // a line of context above the new output
+        adjustment.set_value((row - 1) as f64);
+    }
+}
+
+fn top_row(terminal: &vte::Terminal) -> i64 {
+    visible_rows(terminal).0
+}
+
+pub fn add_bookmark(term: &Term, terminal: &vte::Terminal, note: Option<String>) {
+    let imp = TermImpl::from_instance(term);
+    let row = top_row(terminal);
+    if let Some(meta) = imp.tabs.borrow_mut().by_terminal_mut(terminal) {
+        let index = meta
+            .bookmarks
+            .binary_search_by_key(&row, |(row, _)| *row)
+            .unwrap_or_else(|index| index);
+        match meta.bookmarks.get_mut(index) {
+            Some(bookmark) if bookmark.0 == row => bookmark.1 = note,
+            _ => meta.bookmarks.insert(index, (row, note)),
+        }
+    }
+    terminal.queue_draw();
+}
+
+// scrolls to the closest bookmark below (`forward`) or above the top row
+pub fn jump_to_bookmark(term: &Term, terminal: &vte::Terminal, forward: bool) {
+    let imp = TermImpl::from_instance(term);
+    let top = top_row(terminal);
+    let row = match imp.tabs.borrow().by_terminal(terminal) {
+        Some(meta) if forward => meta
+            .bookmarks
+            .iter()
+            .map(|(row, _)| *row)
+            .find(|&row| row > top),
+        Some(meta) => meta
+            .bookmarks
+            .iter()
+            .rev()
+            .map(|(row, _)| *row)
+            .find(|&row| row < top),
+        None => None,
+    };
+    if let (Some(row), Some(adjustment)) = (row, terminal.vadjustment()) {
+        adjustment.set_value(row as f64);
+    }
+}
+
+// asks for an optional note and bookmarks the top row of the active terminal
+pub fn prompt_bookmark(term: &Term) {
+    let terminal = match term.active_terminal() {
+        Some(terminal) => terminal,
+        None => return,
+    };
+    let dialog = gtk::Dialog::with_buttons(
+        Some("Add Mark"),
+        Some(term),
+        gtk::DialogFlags::MODAL | gtk::DialogFlags::DESTROY_WITH_PARENT,
+        &[
+            ("Cancel", gtk::ResponseType::Cancel),
+            ("Add", gtk::ResponseType::Ok),
+        ],
+    );
+    dialog.set_default_response(gtk::ResponseType::Ok);
+    let note = gtk::Entry::builder()
+        .placeholder_text("note (optional)")
+        .activates_default(true)
+        .build();
+    dialog.content_area().pack_start(&note, false, false, 6);
+
+    dialog.connect_response(
+        glib::clone!(@weak term, @weak terminal, @weak note => move |dialog, response| {
+            if response == gtk::ResponseType::Ok {
+                let text = note.text().trim().to_string();
+                add_bookmark(&term, &terminal, Some(text).filter(|text| !text.is_empty()));
+            }
+            dialog.close();
+        }),
+    );
+    dialog.show_all();
+}
+
+// a bar in the left padding and the note at the end of the row
+fn draw_bookmarks(ctx: &cairo::Context, terminal: &vte::Terminal, meta: &Meta, color: gdk::RGBA) {
+    let height = terminal.char_height() as f64;
+    for (row, note) in &meta.bookmarks {
+        let y = match row_y(terminal, *row) {
+            Some(y) => y,
+            None => continue,
+        };
+        ctx.set_source_rgba(color.red, color.green, color.blue, 0.9);
+        ctx.rectangle(0.0, y, 3.0, height);
+        let _ = ctx.fill();
+        if let Some(note) = note {
+            ctx.select_font_face("sans", cairo::FontSlant::Italic, cairo::FontWeight::Normal);
+            ctx.set_font_size(height * 0.7);
+            if let Ok(extents) = ctx.text_extents(note) {
+                let x = terminal.allocated_width() as f64 - extents.x_advance - 8.0;
+                ctx.move_to(x, y + height * 0.8);
+                let _ = ctx.show_text(note);
+            }
+        }
+    }
+}
+
+// a thick outline reaching past the cursor cell, black or white against the
+// background, and with the crosshair a band across the row
+fn draw_big_cursor(
+    ctx: &cairo::Context,
+    terminal: &vte::Terminal,
+    background: gdk::RGBA,
+    crosshair: bool,
+) {
+    let (column, row) = terminal.cursor_position();
+    let y = match row_y(terminal, row as i64) {
+        Some(y) => y,
+        None => return,
+    };
+    let padding = terminal.style_context().padding(terminal.state_flags());
+    let (width, height) = (terminal.char_width() as f64, terminal.char_height() as f64);
+    let x = padding.left as f64 + column as f64 * width;
+    let color = if luminance(&background) > 0.5 {
+        gdk::RGBA::black()
+    } else {
+        gdk::RGBA::white()
+    };
+
+    if crosshair {
+        ctx.set_source_rgba(color.red, color.green, color.blue, 0.12);
+        ctx.rectangle(0.0, y, terminal.allocated_width() as f64, height);
+        let _ = ctx.fill();
+    }
+    let margin = 3.0;
+    ctx.set_source_rgba(color.red, color.green, color.blue, 1.0);
+    ctx.set_line_width(3.0);
+    ctx.rectangle(
+        x - margin,
+        y - margin,
+        width + 2.0 * margin,
+        height + 2.0 * margin,
+    );
+    let _ = ctx.stroke();
+}
+
+fn fill_row(ctx: &cairo::Context, terminal: &vte::Terminal, y: f64, color: &gdk::RGBA) {
+    ctx.set_source_rgba(color.red, color.green, color.blue, color.alpha);
+    ctx.rectangle(
+        0.0,
+        y,
+        terminal.allocated_width() as f64,
+        terminal.char_height() as f64,
+    );
+    let _ = ctx.fill();
+}
+
+// connected to draw after vte's own handler
+pub fn draw(term: &Term, terminal: &vte::Terminal, ctx: &cairo::Context) {
+    let imp = TermImpl::from_instance(term);
+
+    let config = imp.config.borrow();
+    let colors = (config.background, config.foreground);
+    // blue from the palette, if there's one
+    let accent = config.palette.get(4).copied().unwrap_or(config.foreground);
+    let rules = &config.highlights;
+    if !rules.is_empty() {
+        let (top, rows) = visible_rows(terminal);
+        for row in top..top + rows {
+            let (y, text) = match (row_y(terminal, row), hacks::text_row(terminal, row)) {
+                (Some(y), Some(text)) => (y, text),
+                _ => continue,
+            };
+            // the first rule that matches wins
+            if let Some(rule) = rules.iter().find(|rule| rule.regex.is_match(&text)) {
+                fill_row(ctx, terminal, y, &rule.color);
+            }
+        }
+    }
+
+    let tabs = imp.tabs.borrow();
+    let meta = match tabs.by_terminal(terminal) {
+        Some(meta) => meta,
+        None => return,
+    };
+    for (row, color) in &meta.highlights {
+        if let Some(y) = row_y(terminal, *row) {
+            fill_row(ctx, terminal, y, color);
+        }
+    }
+    if let Some(y) = new_output_marker(terminal, meta).and_then(|row| row_y(terminal, row)) {
+        let (_, foreground) = colors;
+        ctx.set_source_rgba(foreground.red, foreground.green, foreground.blue, 0.4);
+        ctx.set_line_width(1.0);
+        ctx.move_to(0.0, y.floor() + 0.5);
+        ctx.line_to(terminal.allocated_width() as f64, y.floor() + 0.5);
+        let _ = ctx.stroke();
+    }
+    // yellow from the palette
+    let found = config.palette.get(3).copied().unwrap_or(config.foreground);
+    search::draw(ctx, terminal, meta, found);
+    select::draw(ctx, terminal, meta, config.foreground);
+    draw_bookmarks(ctx, terminal, meta, accent);
+    if meta.show_timestamps {
+        draw_timestamps(ctx, terminal, meta, colors);
+    }
+    if !config.badge_template.is_empty() {
+        let badge = shell::fill(&config.badge_template, &meta.placeholders());
+        draw_badge(ctx, terminal, badge.trim(), config.foreground);
+    }
+    if config.redact {
+        redact::draw(ctx, terminal, &config.redactions, config.foreground);
+    }
+    // on top of everything else
+    if config.big_cursor && terminal.is_focus() && terminal.has_focus() {
+        draw_big_cursor(ctx, terminal, config.background, config.cursor_crosshair);
+    }
+}
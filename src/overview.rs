@@ -0,0 +1,155 @@
+use std::rc::Rc;
+use std::time::Duration;
+
+use gtk::cairo;
+use gtk::prelude::*;
+
+use crate::{split, Term};
+
+const THUMBNAIL_WIDTH: i32 = 320;
+const THUMBNAIL_HEIGHT: i32 = 200;
+const REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+
+fn snapshot(page: &gtk::Widget) -> Option<gtk::gdk_pixbuf::Pixbuf> {
+    let width = page.allocated_width();
+    let height = page.allocated_height();
+    if width <= 0 || height <= 0 {
+        return None;
+    }
+    let scale = f64::min(
+        THUMBNAIL_WIDTH as f64 / width as f64,
+        THUMBNAIL_HEIGHT as f64 / height as f64,
+    );
+    let thumb_width = (width as f64 * scale) as i32;
+    let thumb_height = (height as f64 * scale) as i32;
+
+    let surface =
+        cairo::ImageSurface::create(cairo::Format::ARgb32, thumb_width, thumb_height).ok()?;
+    {
+        let ctx = cairo::Context::new(&surface).ok()?;
+        ctx.scale(scale, scale);
+        page.draw(&ctx);
+    }
+    gdk::pixbuf_get_from_surface(&surface, 0, 0, thumb_width, thumb_height)
+}
+
+// builds a filter key out of everything the user might type to find a tab
+fn search_key(term: &Term, page: &gtk::Widget) -> String {
+    split::terminals(page)
+        .iter()
+        .map(|terminal| {
+            let title = terminal
+                .window_title()
+                .map(|s| s.to_string())
+                .unwrap_or_default();
+            let cwd = term
+                .get_terminal_cwd(terminal)
+                .map(|path| path.display().to_string())
+                .unwrap_or_default();
+            format!("{} {}", title, cwd).to_lowercase()
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+pub fn show(term: &Term) {
+    let notebook = term.notebook();
+    let pages = notebook.children();
+    let keys: Rc<Vec<String>> = Rc::new(pages.iter().map(|page| search_key(term, page)).collect());
+
+    let window = gtk::Window::builder()
+        .transient_for(term)
+        .modal(true)
+        .destroy_with_parent(true)
+        .title("Tabs")
+        .default_width(THUMBNAIL_WIDTH * 3)
+        .default_height(THUMBNAIL_HEIGHT * 3)
+        .build();
+
+    let entry = gtk::SearchEntry::new();
+    let flowbox = gtk::FlowBox::builder()
+        .homogeneous(true)
+        .selection_mode(gtk::SelectionMode::Single)
+        .activate_on_single_click(true)
+        .valign(gtk::Align::Start)
+        .build();
+
+    let mut images = Vec::new();
+    for (number, page) in pages.iter().enumerate() {
+        let image = gtk::Image::new();
+        image.set_from_pixbuf(snapshot(page).as_ref());
+        let title = term
+            .focused_terminal(page)
+            .and_then(|terminal| terminal.window_title())
+            .map(|s| s.to_string())
+            .unwrap_or_else(|| "(untitled)".to_string());
+        let label = gtk::Label::builder()
+            .label(&format!("{}. {}", number + 1, title))
+            .ellipsize(pango::EllipsizeMode::Middle)
+            .max_width_chars(40)
+            .build();
+        let vbox = gtk::Box::new(gtk::Orientation::Vertical, 4);
+        vbox.pack_start(&image, false, false, 0);
+        vbox.pack_start(&label, false, false, 0);
+        flowbox.add(&vbox);
+        images.push(image);
+    }
+
+    flowbox.set_filter_func(Some(Box::new(
+        glib::clone!(@weak entry => @default-return true, move |child| {
+            let query = entry.text().to_lowercase();
+            keys.get(child.index() as usize)
+                .map(|key| key.contains(query.trim()))
+                .unwrap_or(true)
+        }),
+    )));
+
+    entry.connect_search_changed(glib::clone!(@weak flowbox => move |_| {
+        flowbox.invalidate_filter();
+    }));
+    entry.connect_activate(glib::clone!(@weak flowbox => move |_| {
+        let first = flowbox
+            .children()
+            .into_iter()
+            .filter_map(|child| child.downcast::<gtk::FlowBoxChild>().ok())
+            .find(|child| child.is_child_visible() && child.is_visible());
+        if let Some(child) = first {
+            child.activate();
+        }
+    }));
+    flowbox.connect_child_activated(
+        glib::clone!(@weak notebook, @weak window => move |_, child| {
+            notebook.set_current_page(Some(child.index() as u32));
+            window.close();
+        }),
+    );
+    window.connect_key_press_event(|window, event| {
+        if event.keyval() == gdk::keys::constants::Escape {
+            window.close();
+            return gtk::Inhibit(true);
+        }
+        gtk::Inhibit(false)
+    });
+
+    let refresh = glib::timeout_add_local(REFRESH_INTERVAL, move || {
+        for (page, image) in pages.iter().zip(images.iter()) {
+            image.set_from_pixbuf(snapshot(page).as_ref());
+        }
+        glib::Continue(true)
+    });
+    let refresh = std::cell::Cell::new(Some(refresh));
+    window.connect_destroy(move |_| {
+        if let Some(source) = refresh.take() {
+            glib::source_remove(source);
+        }
+    });
+
+    let scrolled = gtk::ScrolledWindow::new(None::<&gtk::Adjustment>, None::<&gtk::Adjustment>);
+    scrolled.add(&flowbox);
+    let vbox = gtk::Box::new(gtk::Orientation::Vertical, 6);
+    vbox.pack_start(&entry, false, false, 0);
+    vbox.pack_start(&scrolled, true, true, 0);
+    window.add(&vbox);
+    window.show_all();
+    entry.grab_focus();
+}
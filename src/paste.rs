@@ -0,0 +1,106 @@
+// Pasting several lines where the terminal reads lines without echoing
+// them, which is almost always a password prompt (sudo, ssh) that would get a
+// command as the password and then run the rest, asks first. vte's own paste-clipboard and
+// paste-primary are stopped and emitted again once the text has been looked at.
+
+use std::cell::Cell;
+use std::rc::Rc;
+
+use gtk::prelude::*;
+use vte::TerminalExt;
+
+use crate::{platform, Term};
+
+pub fn connect(term: &Term, terminal: &vte::Terminal) {
+    // set while pasting for real
+    let allowed = Rc::new(Cell::new(false));
+    terminal.connect_paste_clipboard(
+        glib::clone!(@weak term, @strong allowed => move |terminal| {
+            guard(&term, terminal, &allowed, &gdk::SELECTION_CLIPBOARD, "paste-clipboard");
+        }),
+    );
+    terminal.connect_paste_primary(
+        glib::clone!(@weak term, @strong allowed => move |terminal| {
+            guard(&term, terminal, &allowed, &gdk::SELECTION_PRIMARY, "paste-primary");
+        }),
+    );
+}
+
+fn guard(
+    term: &Term,
+    terminal: &vte::Terminal,
+    allowed: &Rc<Cell<bool>>,
+    selection: &gdk::Atom,
+    signal: &'static str,
+) {
+    if allowed.get() {
+        return;
+    }
+    let password_prompt = terminal
+        .pty()
+        .and_then(|pty| platform::password_prompt(pty.fd()))
+        .unwrap_or(false);
+    if !password_prompt {
+        return;
+    }
+    terminal.stop_signal_emission(signal);
+    let clipboard = gtk::Clipboard::get(selection);
+    clipboard.request_text(
+        glib::clone!(@weak term, @weak terminal, @strong allowed => move |_, text| {
+            let lines = match text {
+                Some(text) => text.trim_end().lines().count(),
+                None => return,
+            };
+            if lines < 2 {
+                paste(&terminal, &allowed, signal);
+                return;
+            }
+            confirm(&term, &terminal, &allowed, signal, lines);
+        }),
+    );
+}
+
+fn paste(terminal: &vte::Terminal, allowed: &Cell<bool>, signal: &str) {
+    allowed.set(true);
+    if signal == "paste-primary" {
+        terminal.paste_primary();
+    } else {
+        terminal.paste_clipboard();
+    }
+    allowed.set(false);
+}
+
+fn confirm(
+    term: &Term,
+    terminal: &vte::Terminal,
+    allowed: &Rc<Cell<bool>>,
+    signal: &'static str,
+    lines: usize,
+) {
+    let dialog = gtk::MessageDialog::builder()
+        .transient_for(term)
+        .modal(true)
+        .message_type(gtk::MessageType::Warning)
+        .buttons(gtk::ButtonsType::None)
+        .text(&format!("Paste {} lines at a password prompt?", lines))
+        .secondary_text(
+            "The terminal isn't echoing what's typed, the first line would likely be \
+             taken as a password and the rest run as commands.",
+        )
+        .build();
+    dialog.add_buttons(&[
+        ("Cancel", gtk::ResponseType::Cancel),
+        ("Paste Anyway", gtk::ResponseType::Accept),
+    ]);
+    dialog.set_default_response(gtk::ResponseType::Cancel);
+    dialog.connect_response(
+        glib::clone!(@weak terminal, @strong allowed => move |dialog, response| {
+            dialog.close();
+            terminal.grab_focus();
+            if response == gtk::ResponseType::Accept {
+                paste(&terminal, &allowed, signal);
+            }
+        }),
+    );
+    dialog.show();
+}
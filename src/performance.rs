@@ -0,0 +1,148 @@
+// [performance]: how fast the wheel scrolls, whether output is painted right
+// away or at the next frame, and whether pterm's own work on output waits
+// until vte is done. "Show Frame Timing" puts numbers on the effect.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use gtk::cairo;
+use gtk::prelude::*;
+use gtk::subclass::prelude::*;
+use vte::TerminalExt;
+
+use crate::{Term, TermImpl};
+
+// microseconds of frame clock time the overlay looks back
+const WINDOW: i64 = 1_000_000;
+
+#[derive(Debug, Default)]
+pub struct Frames {
+    // when vte started drawing the frame being drawn
+    started: Option<Instant>,
+    // frame clock time and how long drawing took
+    drawn: VecDeque<(i64, Duration)>,
+}
+
+pub fn toggle_overlay(term: &Term) {
+    let terminal = match term.active_terminal() {
+        Some(terminal) => terminal,
+        None => return,
+    };
+    let imp = TermImpl::from_instance(term);
+    if let Some(meta) = imp.tabs.borrow_mut().by_terminal_mut(&terminal) {
+        meta.frames = match meta.frames {
+            Some(_) => None,
+            None => Some(Frames::default()),
+        };
+    }
+    terminal.queue_draw();
+}
+
+// before vte draws
+pub fn begin_frame(term: &Term, terminal: &vte::Terminal) {
+    let imp = TermImpl::from_instance(term);
+    let mut tabs = imp.tabs.borrow_mut();
+    if let Some(frames) = tabs
+        .by_terminal_mut(terminal)
+        .and_then(|meta| meta.frames.as_mut())
+    {
+        frames.started = Some(Instant::now());
+    }
+}
+
+// after vte and the decorations, so they're counted too
+pub fn end_frame(term: &Term, terminal: &vte::Terminal, ctx: &cairo::Context) {
+    let imp = TermImpl::from_instance(term);
+    let mut tabs = imp.tabs.borrow_mut();
+    let frames = match tabs
+        .by_terminal_mut(terminal)
+        .and_then(|meta| meta.frames.as_mut())
+    {
+        Some(frames) => frames,
+        None => return,
+    };
+    let now = match terminal.frame_clock() {
+        Some(clock) => clock.frame_time(),
+        None => return,
+    };
+    if let Some(started) = frames.started.take() {
+        frames.drawn.push_back((now, started.elapsed()));
+    }
+    while matches!(frames.drawn.front(), Some((time, _)) if now - time > WINDOW) {
+        frames.drawn.pop_front();
+    }
+    let count = frames.drawn.len() as u32;
+    let longest = frames
+        .drawn
+        .iter()
+        .map(|(_, took)| *took)
+        .max()
+        .unwrap_or_default();
+    let total: Duration = frames.drawn.iter().map(|(_, took)| *took).sum();
+    let average = total.checked_div(count).unwrap_or_default();
+    let text = format!(
+        "{} fps, {:.1} ms to draw, {:.1} ms at most",
+        count,
+        average.as_secs_f64() * 1000.0,
+        longest.as_secs_f64() * 1000.0
+    );
+
+    let config = imp.config.borrow();
+    let (background, foreground) = (config.background, config.foreground);
+    ctx.select_font_face(
+        "monospace",
+        cairo::FontSlant::Normal,
+        cairo::FontWeight::Normal,
+    );
+    ctx.set_font_size(terminal.char_height() as f64 * 0.75);
+    let extents = match ctx.text_extents(&text) {
+        Ok(extents) => extents,
+        Err(_) => return,
+    };
+    let height = terminal.char_height() as f64;
+    ctx.set_source_rgba(background.red, background.green, background.blue, 0.85);
+    ctx.rectangle(0.0, 0.0, extents.x_advance + 8.0, height);
+    let _ = ctx.fill();
+    ctx.set_source_rgba(foreground.red, foreground.green, foreground.blue, 0.9);
+    ctx.move_to(4.0, height * 0.8);
+    let _ = ctx.show_text(&text);
+}
+
+// scroll_speed: the scrollback moves that many times what vte would scroll,
+// a tenth of the page a notch. Without scrollback (the alternate screen)
+// vte's turning the wheel into arrow keys is left alone.
+pub fn scroll(term: &Term, terminal: &vte::Terminal, event: &gdk::EventScroll) -> bool {
+    let speed = TermImpl::from_instance(term).config.borrow().scroll_speed;
+    if (speed - 1.0).abs() < f64::EPSILON {
+        return false;
+    }
+    let adjustment = match terminal.vadjustment() {
+        Some(adjustment) => adjustment,
+        None => return false,
+    };
+    if adjustment.upper() <= adjustment.page_size() {
+        return false;
+    }
+    let notches = match event.direction() {
+        gdk::ScrollDirection::Up => -1.0,
+        gdk::ScrollDirection::Down => 1.0,
+        gdk::ScrollDirection::Smooth => event.delta().1,
+        _ => return false,
+    };
+    let step = (adjustment.page_size() / 10.0).ceil().max(1.0);
+    let value = adjustment.value() + notches * step * speed;
+    adjustment.set_value(value.clamp(
+        adjustment.lower(),
+        adjustment.upper() - adjustment.page_size(),
+    ));
+    true
+}
+
+// sync_to_vblank = false: paints what vte just took in now, rather than when
+// the frame clock comes round
+#[allow(deprecated)]
+pub fn paint_now(terminal: &vte::Terminal) {
+    if let Some(window) = terminal.window() {
+        window.process_updates(false);
+    }
+}
@@ -0,0 +1,178 @@
+// a list narrowed down by fuzzy matching what's typed above it, best matches
+// first; Enter picks the top row, or click one
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use gtk::prelude::*;
+
+use crate::Term;
+
+const WIDTH: i32 = 640;
+const HEIGHT: i32 = 420;
+
+pub struct Item {
+    pub label: String,
+    // shown dimmed after the label, matched too
+    pub detail: Option<String>,
+}
+
+// the letters of `query` in order, anywhere in `candidate`; runs of them and
+// ones starting a word count for more, gaps against
+pub fn score(query: &str, candidate: &str) -> Option<i32> {
+    let candidate: Vec<char> = candidate.to_lowercase().chars().collect();
+    let mut position = 0;
+    let mut previous: Option<usize> = None;
+    let mut score = 0;
+    for wanted in query.to_lowercase().chars().filter(|c| !c.is_whitespace()) {
+        let found = position + candidate[position..].iter().position(|&c| c == wanted)?;
+        if previous
+            .map(|previous| previous + 1 == found)
+            .unwrap_or(false)
+        {
+            score += 5;
+        }
+        if found == 0 || !candidate[found - 1].is_alphanumeric() {
+            score += 3;
+        }
+        score -= (found - position) as i32;
+        previous = Some(found);
+        position = found + 1;
+    }
+    Some(score)
+}
+
+pub fn show(term: &Term, title: &str, items: Vec<Item>, pick: impl Fn(usize) + 'static) {
+    let window = gtk::Window::builder()
+        .transient_for(term)
+        .modal(true)
+        .destroy_with_parent(true)
+        .title(title)
+        .default_width(WIDTH)
+        .default_height(HEIGHT)
+        .build();
+
+    let entry = gtk::SearchEntry::new();
+    let list = gtk::ListBox::builder()
+        .selection_mode(gtk::SelectionMode::Browse)
+        .activate_on_single_click(true)
+        .build();
+    let keys: Vec<String> = items
+        .iter()
+        .map(|item| match &item.detail {
+            Some(detail) => format!("{} {}", item.label, detail),
+            None => item.label.clone(),
+        })
+        .collect();
+    // rows move around when sorted, this is where they are in `items`
+    let mut indices = HashMap::new();
+    for (index, item) in items.iter().enumerate() {
+        let hbox = gtk::Box::new(gtk::Orientation::Horizontal, 12);
+        let label = gtk::Label::builder()
+            .label(&item.label)
+            .xalign(0.0)
+            .ellipsize(pango::EllipsizeMode::End)
+            .build();
+        hbox.pack_start(&label, true, true, 0);
+        if let Some(detail) = &item.detail {
+            let detail = gtk::Label::builder()
+                .label(detail)
+                .ellipsize(pango::EllipsizeMode::Start)
+                .max_width_chars(30)
+                .build();
+            detail.style_context().add_class("dim-label");
+            hbox.pack_end(&detail, false, false, 0);
+        }
+        let row = gtk::ListBoxRow::new();
+        row.add(&hbox);
+        list.add(&row);
+        indices.insert(row, index);
+    }
+    let indices = Rc::new(indices);
+
+    // by index into `items`, None when filtered out
+    let scores: Rc<RefCell<Vec<Option<i32>>>> = Rc::new(RefCell::new(vec![Some(0); items.len()]));
+    list.set_filter_func(Some(Box::new(
+        glib::clone!(@strong indices, @strong scores => move |row| {
+            let index = indices.get(row).copied().unwrap_or_default();
+            scores.borrow().get(index).copied().flatten().is_some()
+        }),
+    )));
+    list.set_sort_func(Some(Box::new(
+        glib::clone!(@strong indices, @strong scores => move |a, b| {
+            let scores = scores.borrow();
+            let index = |row: &gtk::ListBoxRow| indices.get(row).copied().unwrap_or_default();
+            let score = |row: &gtk::ListBoxRow| scores.get(index(row)).copied().flatten();
+            // higher first, ties in the original order
+            score(b).cmp(&score(a)).then(index(a).cmp(&index(b))) as i32
+        }),
+    )));
+
+    entry.connect_search_changed(glib::clone!(@weak list, @strong scores => move |entry| {
+        let query = entry.text();
+        *scores.borrow_mut() = keys.iter().map(|key| score(&query, key)).collect();
+        list.invalidate_filter();
+        list.invalidate_sort();
+        let first = (0..)
+            .map_while(|index| list.row_at_index(index))
+            .find(|row| row.is_child_visible());
+        list.select_row(first.as_ref());
+    }));
+    entry.connect_activate(glib::clone!(@weak list => move |_| {
+        let row = list.selected_row().or_else(|| {
+            (0..)
+                .map_while(|index| list.row_at_index(index))
+                .find(|row| row.is_child_visible())
+        });
+        if let Some(row) = row {
+            row.activate();
+        }
+    }));
+    // Up and Down move through the list without leaving the entry
+    entry.connect_key_press_event(
+        glib::clone!(@weak list => @default-return gtk::Inhibit(false), move |_, event| {
+            let step = match event.keyval() {
+                gdk::keys::constants::Down => 1,
+                gdk::keys::constants::Up => -1,
+                _ => return gtk::Inhibit(false),
+            };
+            let visible: Vec<gtk::ListBoxRow> = (0..)
+                .map_while(|index| list.row_at_index(index))
+                .filter(|row| row.is_child_visible())
+                .collect();
+            let current = list
+                .selected_row()
+                .and_then(|selected| visible.iter().position(|row| *row == selected));
+            let next = match current {
+                Some(current) => (current as i32 + step).clamp(0, visible.len() as i32 - 1),
+                None => 0,
+            };
+            list.select_row(visible.get(next as usize));
+            gtk::Inhibit(true)
+        }),
+    );
+    list.connect_row_activated(glib::clone!(@weak window => move |_, row| {
+        window.close();
+        if let Some(index) = indices.get(row) {
+            pick(*index);
+        }
+    }));
+    window.connect_key_press_event(|window, event| {
+        if event.keyval() == gdk::keys::constants::Escape {
+            window.close();
+            return gtk::Inhibit(true);
+        }
+        gtk::Inhibit(false)
+    });
+
+    let scrolled = gtk::ScrolledWindow::new(None::<&gtk::Adjustment>, None::<&gtk::Adjustment>);
+    scrolled.add(&list);
+    let vbox = gtk::Box::new(gtk::Orientation::Vertical, 6);
+    vbox.pack_start(&entry, false, false, 0);
+    vbox.pack_start(&scrolled, true, true, 0);
+    window.add(&vbox);
+    window.show_all();
+    list.select_row(list.row_at_index(0).as_ref());
+    entry.grab_focus();
+}
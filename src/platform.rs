@@ -0,0 +1,478 @@
+// Everything that differs between operating systems besides cwd lookup
+// (see cwd.rs) and the control socket (ctl.rs, unix only). The Windows side
+// is stubs that keep the unix code in one place; nobody has built pterm for
+// Windows, and with vte having only a unix pty backend it couldn't run a
+// shell there anyway.
+
+use std::path::PathBuf;
+
+#[cfg(unix)]
+pub const SHELL_VAR: &str = "SHELL";
+#[cfg(windows)]
+pub const SHELL_VAR: &str = "COMSPEC";
+
+// SHELL is often unset under display managers and systemd user services
+#[cfg(unix)]
+fn passwd_shell() -> Option<PathBuf> {
+    use std::os::unix::ffi::OsStrExt;
+
+    unsafe {
+        let pw = libc::getpwuid(libc::getuid());
+        if pw.is_null() || (*pw).pw_shell.is_null() {
+            return None;
+        }
+        let shell = std::ffi::CStr::from_ptr((*pw).pw_shell).to_bytes();
+        if shell.is_empty() {
+            return None;
+        }
+        Some(PathBuf::from(std::ffi::OsStr::from_bytes(shell)))
+    }
+}
+
+#[cfg(unix)]
+pub fn shell() -> PathBuf {
+    glib::getenv(SHELL_VAR)
+        .map(PathBuf::from)
+        .or_else(passwd_shell)
+        .unwrap_or_else(|| PathBuf::from("/bin/sh"))
+}
+
+#[cfg(windows)]
+pub fn shell() -> PathBuf {
+    glib::getenv(SHELL_VAR)
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("cmd.exe"))
+}
+
+// opaque value that changes when a pid is reused, None where we can't tell
+#[cfg(target_os = "linux")]
+pub fn process_start_time(pid: u32) -> Option<u64> {
+    let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    // comm may contain spaces and parens, fields after it are plain numbers
+    let fields = &stat[stat.rfind(')')? + 1..];
+    // starttime is field 22, the first one after comm is field 3
+    fields.split_whitespace().nth(22 - 3)?.parse().ok()
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn process_start_time(_pid: u32) -> Option<u64> {
+    None
+}
+
+// environment the process was started with, as KEY=VALUE strings
+#[cfg(target_os = "linux")]
+pub fn process_environ(pid: u32) -> Option<Vec<String>> {
+    let environ = std::fs::read(format!("/proc/{}/environ", pid)).ok()?;
+    Some(
+        environ
+            .split(|&b| b == 0)
+            .filter(|var| !var.is_empty())
+            .filter_map(|var| String::from_utf8(var.to_vec()).ok())
+            .collect(),
+    )
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn process_environ(_pid: u32) -> Option<Vec<String>> {
+    None
+}
+
+// leader of the foreground process group on the terminal behind `fd`
+#[cfg(unix)]
+pub fn foreground_pid(fd: i32) -> Option<u32> {
+    let pgrp = unsafe { libc::tcgetpgrp(fd) };
+    if pgrp <= 0 {
+        return None;
+    }
+    Some(pgrp as u32)
+}
+
+#[cfg(windows)]
+pub fn foreground_pid(_fd: i32) -> Option<u32> {
+    None
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Signal {
+    Interrupt,
+    Terminate,
+    Kill,
+    Stop,
+    Continue,
+    Hangup,
+}
+
+impl Signal {
+    // short name for the signal_foreground parameter and the menu label
+    pub const ALL: [(Signal, &'static str, &'static str); 5] = [
+        (Signal::Interrupt, "int", "Interrupt (SIGINT)"),
+        (Signal::Terminate, "term", "Terminate (SIGTERM)"),
+        (Signal::Kill, "kill", "Kill (SIGKILL)"),
+        (Signal::Stop, "stop", "Stop (SIGSTOP)"),
+        (Signal::Continue, "cont", "Continue (SIGCONT)"),
+    ];
+
+    pub fn from_name(name: &str) -> Option<Signal> {
+        Self::ALL
+            .iter()
+            .find(|(_, short, _)| *short == name)
+            .map(|(signal, _, _)| *signal)
+    }
+}
+
+#[cfg(unix)]
+fn signal_number(signal: Signal) -> i32 {
+    match signal {
+        Signal::Interrupt => libc::SIGINT,
+        Signal::Terminate => libc::SIGTERM,
+        Signal::Kill => libc::SIGKILL,
+        Signal::Stop => libc::SIGSTOP,
+        Signal::Continue => libc::SIGCONT,
+        Signal::Hangup => libc::SIGHUP,
+    }
+}
+
+// to every process in the group, as the terminal's Ctrl+C does
+#[cfg(unix)]
+pub fn signal_group(pgrp: u32, signal: Signal) -> std::io::Result<()> {
+    if unsafe { libc::killpg(pgrp as libc::pid_t, signal_number(signal)) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+pub fn signal(pid: u32, signal: Signal) -> std::io::Result<()> {
+    if unsafe { libc::kill(pid as libc::pid_t, signal_number(signal)) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+pub fn signal_group(_pgrp: u32, _signal: Signal) -> std::io::Result<()> {
+    Err(std::io::ErrorKind::Unsupported.into())
+}
+
+#[cfg(windows)]
+pub fn signal(_pid: u32, _signal: Signal) -> std::io::Result<()> {
+    Err(std::io::ErrorKind::Unsupported.into())
+}
+
+// whether the terminal behind `fd` reads lines without echoing them, as
+// password prompts do; full screen programs turn off both ECHO and ICANON
+#[cfg(unix)]
+pub fn password_prompt(fd: i32) -> Option<bool> {
+    let mut termios = std::mem::MaybeUninit::<libc::termios>::uninit();
+    if unsafe { libc::tcgetattr(fd, termios.as_mut_ptr()) } != 0 {
+        return None;
+    }
+    let termios = unsafe { termios.assume_init() };
+    Some(termios.c_lflag & libc::ECHO == 0 && termios.c_lflag & libc::ICANON != 0)
+}
+
+#[cfg(windows)]
+pub fn password_prompt(_fd: i32) -> Option<bool> {
+    None
+}
+
+// software flow control (IXON) settings of the terminal behind `fd`
+pub struct FlowControl {
+    pub enabled: bool,
+    // IXANY: any key resumes stopped output, not only Ctrl+Q
+    pub any_resumes: bool,
+}
+
+#[cfg(unix)]
+pub fn flow_control(fd: i32) -> Option<FlowControl> {
+    let mut termios = std::mem::MaybeUninit::<libc::termios>::uninit();
+    if unsafe { libc::tcgetattr(fd, termios.as_mut_ptr()) } != 0 {
+        return None;
+    }
+    let termios = unsafe { termios.assume_init() };
+    Some(FlowControl {
+        enabled: termios.c_iflag & libc::IXON != 0,
+        any_resumes: termios.c_iflag & libc::IXANY != 0,
+    })
+}
+
+#[cfg(windows)]
+pub fn flow_control(_fd: i32) -> Option<FlowControl> {
+    None
+}
+
+pub struct Process {
+    pub pid: u32,
+    pub ppid: u32,
+    pub name: String,
+    // user and system time so far, in seconds
+    pub cpu_time: f64,
+    // as process_start_time has it
+    pub start_time: Option<u64>,
+}
+
+// every process we can see
+#[cfg(target_os = "linux")]
+pub fn processes() -> Vec<Process> {
+    let ticks = unsafe { libc::sysconf(libc::_SC_CLK_TCK) }.max(1) as f64;
+    let entries = match std::fs::read_dir("/proc") {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+    entries
+        .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse::<u32>().ok())
+        .filter_map(|pid| {
+            let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+            // the name is in parens and may contain anything, even ") "
+            let open = stat.find('(')?;
+            let close = stat.rfind(')')?;
+            let name = stat[open + 1..close].to_string();
+            let fields: Vec<&str> = stat[close + 1..].split_whitespace().collect();
+            let (utime, stime): (f64, f64) =
+                (fields.get(11)?.parse().ok()?, fields.get(12)?.parse().ok()?);
+            Some(Process {
+                pid,
+                ppid: fields.get(1)?.parse().ok()?,
+                name,
+                cpu_time: (utime + stime) / ticks,
+                start_time: fields.get(19).and_then(|field| field.parse().ok()),
+            })
+        })
+        .collect()
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn processes() -> Vec<Process> {
+    Vec::new()
+}
+
+#[cfg(target_os = "linux")]
+pub fn process_cmdline(pid: u32) -> Option<Vec<String>> {
+    let cmdline = std::fs::read(format!("/proc/{}/cmdline", pid)).ok()?;
+    let argv: Vec<String> = cmdline
+        .split(|&b| b == 0)
+        .filter(|arg| !arg.is_empty())
+        .map(|arg| String::from_utf8_lossy(arg).into_owned())
+        .collect();
+    if argv.is_empty() {
+        return None;
+    }
+    Some(argv)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn process_cmdline(_pid: u32) -> Option<Vec<String>> {
+    None
+}
+
+// `status` is what vte passes to child-exited
+#[cfg(unix)]
+pub fn describe_exit(status: i32) -> String {
+    if libc::WIFEXITED(status) {
+        format!("process exited with code {}", libc::WEXITSTATUS(status))
+    } else if libc::WIFSIGNALED(status) {
+        format!("process killed by signal {}", libc::WTERMSIG(status))
+    } else {
+        format!("process exited with status {}", status)
+    }
+}
+
+#[cfg(windows)]
+pub fn describe_exit(status: i32) -> String {
+    format!("process exited with code {}", status)
+}
+
+// a serial device set to `baud`, 8N1, raw, with the flow control asked for
+#[cfg(unix)]
+pub fn open_serial(
+    path: &std::path::Path,
+    baud: u32,
+    flow: crate::serial::Flow,
+) -> std::io::Result<std::fs::File> {
+    use crate::serial::Flow;
+    use std::os::unix::fs::OpenOptionsExt;
+    use std::os::unix::io::AsRawFd;
+
+    let speed = match baud {
+        1200 => libc::B1200,
+        2400 => libc::B2400,
+        4800 => libc::B4800,
+        9600 => libc::B9600,
+        19200 => libc::B19200,
+        38400 => libc::B38400,
+        57600 => libc::B57600,
+        115200 => libc::B115200,
+        230400 => libc::B230400,
+        #[cfg(target_os = "linux")]
+        460800 => libc::B460800,
+        #[cfg(target_os = "linux")]
+        921600 => libc::B921600,
+        _ => {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("unsupported baud rate {}", baud),
+            ))
+        }
+    };
+    let file = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .custom_flags(libc::O_NOCTTY)
+        .open(path)?;
+    let fd = file.as_raw_fd();
+    let mut termios = std::mem::MaybeUninit::<libc::termios>::uninit();
+    if unsafe { libc::tcgetattr(fd, termios.as_mut_ptr()) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    let mut termios = unsafe { termios.assume_init() };
+    unsafe {
+        libc::cfmakeraw(&mut termios);
+        libc::cfsetispeed(&mut termios, speed);
+        libc::cfsetospeed(&mut termios, speed);
+    }
+    termios.c_cflag |= libc::CLOCAL | libc::CREAD;
+    termios.c_cflag &= !libc::CRTSCTS;
+    termios.c_iflag &= !(libc::IXON | libc::IXOFF | libc::IXANY);
+    match flow {
+        Flow::None => {}
+        Flow::Software => termios.c_iflag |= libc::IXON | libc::IXOFF,
+        Flow::Hardware => termios.c_cflag |= libc::CRTSCTS,
+    }
+    if unsafe { libc::tcsetattr(fd, libc::TCSANOW, &termios) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(file)
+}
+
+#[cfg(windows)]
+pub fn open_serial(
+    _path: &std::path::Path,
+    _baud: u32,
+    _flow: crate::serial::Flow,
+) -> std::io::Result<std::fs::File> {
+    Err(std::io::ErrorKind::Unsupported.into())
+}
+
+// keys reach us one by one and untouched, Ctrl+C and Ctrl+S included; output
+// processing stays, so a bare \n still starts a new line
+#[cfg(unix)]
+pub fn raw_input(fd: i32) -> std::io::Result<()> {
+    let mut termios = std::mem::MaybeUninit::<libc::termios>::uninit();
+    if unsafe { libc::tcgetattr(fd, termios.as_mut_ptr()) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    let mut termios = unsafe { termios.assume_init() };
+    termios.c_lflag &= !(libc::ICANON | libc::ECHO | libc::ISIG | libc::IEXTEN);
+    termios.c_iflag &= !(libc::IXON | libc::ICRNL | libc::INLCR | libc::IGNCR | libc::ISTRIP);
+    termios.c_cc[libc::VMIN] = 1;
+    termios.c_cc[libc::VTIME] = 0;
+    if unsafe { libc::tcsetattr(fd, libc::TCSANOW, &termios) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+pub fn raw_input(_fd: i32) -> std::io::Result<()> {
+    Err(std::io::ErrorKind::Unsupported.into())
+}
+
+#[cfg(unix)]
+fn write_fd(fd: i32, mut bytes: &[u8]) -> std::io::Result<()> {
+    while !bytes.is_empty() {
+        let written =
+            unsafe { libc::write(fd, bytes.as_ptr() as *const libc::c_void, bytes.len()) };
+        if written < 0 {
+            let err = std::io::Error::last_os_error();
+            if err.kind() == std::io::ErrorKind::Interrupted {
+                continue;
+            }
+            return Err(err);
+        }
+        bytes = &bytes[written as usize..];
+    }
+    Ok(())
+}
+
+// copies stdin to `device` through `input` and `device` to stdout until
+// either side is closed
+#[cfg(unix)]
+pub fn relay(
+    device: &std::fs::File,
+    mut input: impl FnMut(&[u8]) -> Vec<u8>,
+) -> std::io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    let (stdin, stdout, device) = (0, 1, device.as_raw_fd());
+    let mut fds = [
+        libc::pollfd {
+            fd: stdin,
+            events: libc::POLLIN,
+            revents: 0,
+        },
+        libc::pollfd {
+            fd: device,
+            events: libc::POLLIN,
+            revents: 0,
+        },
+    ];
+    let mut buffer = [0u8; 4096];
+    loop {
+        if unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, -1) } < 0 {
+            let err = std::io::Error::last_os_error();
+            if err.kind() == std::io::ErrorKind::Interrupted {
+                continue;
+            }
+            return Err(err);
+        }
+        for (index, pollfd) in fds.iter().enumerate() {
+            if pollfd.revents == 0 {
+                continue;
+            }
+            let read = unsafe {
+                libc::read(
+                    pollfd.fd,
+                    buffer.as_mut_ptr() as *mut libc::c_void,
+                    buffer.len(),
+                )
+            };
+            if read < 0 {
+                let err = std::io::Error::last_os_error();
+                if err.kind() == std::io::ErrorKind::Interrupted {
+                    continue;
+                }
+                return Err(err);
+            }
+            let bytes = &buffer[..read as usize];
+            match (index, read) {
+                (0, 0) => return Ok(()),
+                (_, 0) => return Err(std::io::ErrorKind::UnexpectedEof.into()),
+                (0, _) => write_fd(device, &input(bytes))?,
+                _ => write_fd(stdout, bytes)?,
+            }
+        }
+    }
+}
+
+#[cfg(windows)]
+pub fn relay(_device: &std::fs::File, _input: impl FnMut(&[u8]) -> Vec<u8>) -> std::io::Result<()> {
+    Err(std::io::ErrorKind::Unsupported.into())
+}
+
+// a function from the process or a library it's linked with, looked up by name
+// so that one missing from an older version doesn't stop us from starting
+#[cfg(unix)]
+pub fn symbol(name: &str) -> Option<*mut std::ffi::c_void> {
+    let name = std::ffi::CString::new(name).ok()?;
+    let symbol = unsafe { libc::dlsym(libc::RTLD_DEFAULT, name.as_ptr()) };
+    if symbol.is_null() {
+        None
+    } else {
+        Some(symbol)
+    }
+}
+
+#[cfg(windows)]
+pub fn symbol(_name: &str) -> Option<*mut std::ffi::c_void> {
+    None
+}
@@ -0,0 +1,144 @@
+// "Print…": the keyboard selection, or what's on screen, as text in the
+// terminal's font; with print_colors in the terminal's colors, otherwise black
+// on white, which is what paper wants
+
+use std::cell::Cell;
+use std::rc::Rc;
+
+use gtk::prelude::*;
+use gtk::subclass::prelude::*;
+use vte::TerminalExt;
+
+use crate::hacks::{self, CellColors};
+use crate::{marks, select, Term, TermImpl};
+
+// a line of text and the colors of its bytes
+type Line = (String, Vec<CellColors>);
+
+fn content(term: &Term, terminal: &vte::Terminal) -> Option<(String, Vec<CellColors>)> {
+    let (start, end) = select::bounds(term, terminal).unwrap_or_else(|| {
+        let (top, rows) = marks::visible_rows(terminal);
+        ((top, 0), (top + rows - 1, terminal.column_count()))
+    });
+    hacks::text_range_colors(terminal, start, end)
+}
+
+fn lines(text: &str, colors: &[CellColors]) -> Vec<Line> {
+    let mut lines = Vec::new();
+    let mut offset = 0;
+    for line in text.split('\n') {
+        let end = offset + line.len();
+        let line_colors = colors
+            .get(offset..end)
+            .map(<[_]>::to_vec)
+            .unwrap_or_default();
+        lines.push((line.to_string(), line_colors));
+        offset = end + 1;
+    }
+    while lines
+        .last()
+        .map(|(line, _)| line.trim().is_empty())
+        .unwrap_or(false)
+    {
+        lines.pop();
+    }
+    lines
+}
+
+fn page<'a>(
+    lines: impl Iterator<Item = &'a Line>,
+    print_colors: bool,
+) -> (String, pango::AttrList) {
+    let mut text = String::new();
+    let mut colors: Vec<Option<CellColors>> = Vec::new();
+    for (line, line_colors) in lines {
+        if !text.is_empty() {
+            text.push('\n');
+            colors.push(None);
+        }
+        text.push_str(line);
+        colors.extend((0..line.len()).map(|index| line_colors.get(index).copied()));
+    }
+
+    let attributes = pango::AttrList::new();
+    let add = |attribute: Option<pango::Attribute>, start: usize, end: usize| {
+        if let Some(mut attribute) = attribute {
+            attribute.set_start_index(start as u32);
+            attribute.set_end_index(end as u32);
+            attributes.insert(attribute);
+        }
+    };
+    if !print_colors {
+        add(pango::Attribute::new_foreground(0, 0, 0), 0, text.len());
+        return (text, attributes);
+    }
+    // runs of the same colors
+    let mut start = 0;
+    while start < colors.len() {
+        let end = (start..colors.len())
+            .find(|&index| colors[index] != colors[start])
+            .unwrap_or(colors.len());
+        if let Some((fore, back)) = colors[start] {
+            add(
+                pango::Attribute::new_foreground(fore[0], fore[1], fore[2]),
+                start,
+                end,
+            );
+            add(
+                pango::Attribute::new_background(back[0], back[1], back[2]),
+                start,
+                end,
+            );
+        }
+        start = end;
+    }
+    (text, attributes)
+}
+
+pub fn print(term: &Term) {
+    let terminal = match term.active_terminal() {
+        Some(terminal) => terminal,
+        None => return,
+    };
+    let lines = match content(term, &terminal) {
+        Some((text, colors)) => Rc::new(lines(&text, &colors)),
+        None => return,
+    };
+    let (font, print_colors, background) = {
+        let config = TermImpl::from_instance(term).config.borrow();
+        (config.font.clone(), config.print_colors, config.background)
+    };
+
+    let operation = gtk::PrintOperation::new();
+    let title = terminal.window_title().map(|title| title.to_string());
+    operation.set_job_name(title.as_deref().unwrap_or("pterm"));
+    let per_page = Rc::new(Cell::new(1));
+    operation.connect_begin_print(
+        glib::clone!(@strong lines, @strong per_page, @strong font => move |operation, context| {
+            let layout = context.create_pango_layout();
+            layout.set_font_description(Some(&font));
+            layout.set_text("X");
+            let height = layout.size().1 as f64 / pango::SCALE as f64;
+            let fit = (context.height() / height.max(1.0)).floor().max(1.0) as usize;
+            per_page.set(fit);
+            operation.set_n_pages(((lines.len() + fit - 1) / fit).max(1) as i32);
+        }),
+    );
+    operation.connect_draw_page(glib::clone!(@weak term => move |_, context, number| {
+        let fit = per_page.get();
+        let (text, attributes) = page(lines.iter().skip(number as usize * fit).take(fit), print_colors);
+        let layout = context.create_pango_layout();
+        layout.set_font_description(Some(&font));
+        layout.set_text(&text);
+        layout.set_attributes(Some(&attributes));
+        let cr = context.cairo_context();
+        if print_colors {
+            cr.set_source_rgb(background.red, background.green, background.blue);
+            let _ = cr.paint();
+        }
+        gtk::render_layout(&term.style_context(), &cr, 0.0, 0.0, &layout);
+    }));
+    if let Err(err) = operation.run(gtk::PrintOperationAction::PrintDialog, Some(term)) {
+        term.show_warning(&format!("can't print: {}", err));
+    }
+}
@@ -0,0 +1,296 @@
+// what a tab is running: the process tree under its child with the cpu each
+// process used since the last refresh, and a button to kill any of them
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use gtk::prelude::*;
+use gtk::subclass::prelude::*;
+
+use crate::platform::{self, Process, Signal};
+use crate::{hacks, Term, TermImpl};
+
+const REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+// how often busy_cpu looks at every tab, reading /proc isn't free
+const BUSY_INTERVAL: Duration = Duration::from_secs(3);
+
+// `root` and everything under it, parents first, with their depth
+pub fn tree(processes: &[Process], root: u32) -> Vec<(usize, &Process)> {
+    let mut children: HashMap<u32, Vec<&Process>> = HashMap::new();
+    for process in processes {
+        children.entry(process.ppid).or_default().push(process);
+    }
+    let mut tree = Vec::new();
+    let mut stack: Vec<(usize, &Process)> = processes
+        .iter()
+        .filter(|process| process.pid == root)
+        .map(|process| (0, process))
+        .collect();
+    while let Some((depth, process)) = stack.pop() {
+        tree.push((depth, process));
+        if let Some(children) = children.get(&process.pid) {
+            // popped in pid order
+            let mut children = children.clone();
+            children.sort_by_key(|child| std::cmp::Reverse(child.pid));
+            stack.extend(children.into_iter().map(|child| (depth + 1, child)));
+        }
+    }
+    tree
+}
+
+// only while `pid` is still the process its row was made for and still under
+// `root`, pids get reused
+fn kill(root: u32, pid: u32, start_time: Option<u64>) {
+    let processes = platform::processes();
+    let ours = tree(&processes, root)
+        .iter()
+        .any(|(_, process)| process.pid == pid && process.start_time == start_time);
+    if !ours {
+        log::info!(
+            "not killing {}, it's gone or isn't in this tab anymore",
+            pid
+        );
+        return;
+    }
+    if let Err(err) = platform::signal(pid, Signal::Terminate) {
+        log::warn!("can't kill {}: {}", pid, err);
+    }
+}
+
+// cpu times from the last refresh, to tell the usage since then
+struct Sample {
+    at: Instant,
+    cpu_times: HashMap<u32, f64>,
+}
+
+// a process's widgets, kept for as long as it lives so that a refresh only
+// moves them and updates the cpu column
+struct Row {
+    start_time: Option<u64>,
+    top: i32,
+    depth: usize,
+    command: String,
+    label: gtk::Label,
+    cpu: gtk::Label,
+    widgets: [gtk::Widget; 4],
+}
+
+impl Row {
+    fn new(grid: &gtk::Grid, root: u32, top: i32, depth: usize, process: &Process) -> Row {
+        let command = platform::process_cmdline(process.pid)
+            .map(|argv| argv.join(" "))
+            .unwrap_or_else(|| process.name.clone());
+        let label = gtk::Label::builder()
+            .label(&format!("{}{}", "  ".repeat(depth), command))
+            .xalign(0.0)
+            .ellipsize(pango::EllipsizeMode::End)
+            .max_width_chars(50)
+            .build();
+        let pid = gtk::Label::builder()
+            .label(&process.pid.to_string())
+            .xalign(1.0)
+            .build();
+        let cpu = gtk::Label::builder().xalign(1.0).build();
+        let button =
+            gtk::Button::from_icon_name(Some("process-stop-symbolic"), gtk::IconSize::Menu);
+        button.set_relief(gtk::ReliefStyle::None);
+        button.set_tooltip_text(Some("Kill"));
+        let (target, start_time) = (process.pid, process.start_time);
+        button.connect_clicked(move |_| kill(root, target, start_time));
+
+        let widgets = [
+            label.clone().upcast(),
+            pid.upcast(),
+            cpu.clone().upcast(),
+            button.upcast(),
+        ];
+        for (column, widget) in widgets.iter().enumerate() {
+            grid.attach(widget, column as i32, top, 1, 1);
+        }
+        Row {
+            start_time,
+            top,
+            depth,
+            command,
+            label,
+            cpu,
+            widgets,
+        }
+    }
+}
+
+fn fill(grid: &gtk::Grid, root: u32, last: &mut Sample, rows: &mut HashMap<u32, Row>) {
+    let processes = platform::processes();
+    let elapsed = last.at.elapsed().as_secs_f64().max(0.001);
+    if grid.children().is_empty() {
+        for (index, title) in ["Command", "PID", "CPU", ""].iter().enumerate() {
+            let label = gtk::Label::builder().label(title).xalign(0.0).build();
+            label.style_context().add_class("dim-label");
+            grid.attach(&label, index as i32, 0, 1, 1);
+        }
+    }
+    let tree = tree(&processes, root);
+    rows.retain(|pid, row| {
+        let alive = tree
+            .iter()
+            .any(|(_, process)| process.pid == *pid && process.start_time == row.start_time);
+        if !alive {
+            for widget in &row.widgets {
+                grid.remove(widget);
+            }
+        }
+        alive
+    });
+    for (index, (depth, process)) in tree.into_iter().enumerate() {
+        let top = index as i32 + 1;
+        let row = rows
+            .entry(process.pid)
+            .or_insert_with(|| Row::new(grid, root, top, depth, process));
+        if row.top != top {
+            for widget in &row.widgets {
+                hacks::set_child_property(grid.clone(), widget.clone(), "top-attach", top);
+            }
+            row.top = top;
+        }
+        if row.depth != depth {
+            row.label
+                .set_label(&format!("{}{}", "  ".repeat(depth), row.command));
+            row.depth = depth;
+        }
+        let used = last
+            .cpu_times
+            .get(&process.pid)
+            .map(|before| (process.cpu_time - before) / elapsed * 100.0)
+            .unwrap_or(0.0);
+        row.cpu.set_label(&format!("{:.0}%", used.max(0.0)));
+    }
+    grid.show_all();
+    *last = Sample {
+        at: Instant::now(),
+        cpu_times: processes
+            .iter()
+            .map(|process| (process.pid, process.cpu_time))
+            .collect(),
+    };
+}
+
+pub fn show(term: &Term) {
+    let imp = TermImpl::from_instance(term);
+    let terminal = match term.active_terminal() {
+        Some(terminal) => terminal,
+        None => return,
+    };
+    let root = match imp
+        .tabs
+        .borrow()
+        .by_terminal(&terminal)
+        .and_then(|meta| meta.live_pid())
+    {
+        Some(pid) => pid,
+        None => return,
+    };
+
+    let grid = gtk::Grid::builder()
+        .column_spacing(12)
+        .row_spacing(2)
+        .margin(6)
+        .build();
+    let mut last = Sample {
+        at: Instant::now(),
+        cpu_times: HashMap::new(),
+    };
+    let mut rows = HashMap::new();
+    fill(&grid, root, &mut last, &mut rows);
+
+    let popover = gtk::Popover::new(Some(&terminal));
+    popover.set_pointing_to(&gdk::Rectangle {
+        x: terminal.allocated_width() / 2,
+        y: 0,
+        width: 1,
+        height: 1,
+    });
+    popover.set_position(gtk::PositionType::Bottom);
+    popover.add(&grid);
+
+    let source = glib::timeout_add_local(
+        REFRESH_INTERVAL,
+        glib::clone!(@weak grid => @default-return glib::Continue(false), move || {
+            fill(&grid, root, &mut last, &mut rows);
+            glib::Continue(true)
+        }),
+    );
+    let source = std::cell::Cell::new(Some(source));
+    popover.connect_closed(move |popover| {
+        if let Some(source) = source.take() {
+            glib::source_remove(source);
+        }
+        popover.destroy();
+    });
+    popover.popup();
+}
+
+// with busy_cpu set, tabs whose processes together use more than that many
+// percent of a core get it in their label; called again on every config load
+pub fn watch_busy(term: &Term) {
+    let imp = TermImpl::from_instance(term);
+    if let Some(source) = imp.busy_watch.take() {
+        glib::source_remove(source);
+    }
+    if imp.config.borrow().busy_cpu.is_none() {
+        // takes the label off tabs that were busy before
+        update_busy(term);
+        return;
+    }
+    let source = glib::timeout_add_local(
+        BUSY_INTERVAL,
+        glib::clone!(@weak term => @default-return glib::Continue(false), move || {
+            update_busy(&term);
+            glib::Continue(true)
+        }),
+    );
+    imp.busy_watch.set(Some(source));
+}
+
+fn update_busy(term: &Term) {
+    let imp = TermImpl::from_instance(term);
+    let threshold = imp.config.borrow().busy_cpu;
+    let terminals: Vec<vte::Terminal> = imp.tabs.borrow().terminals().cloned().collect();
+    let processes = match threshold {
+        Some(_) => platform::processes(),
+        None => Vec::new(),
+    };
+    let now = Instant::now();
+    for terminal in &terminals {
+        let changed = {
+            let mut tabs = imp.tabs.borrow_mut();
+            let meta = match tabs.by_terminal_mut(terminal) {
+                Some(meta) if !meta.hibernating => meta,
+                _ => continue,
+            };
+            let cpu_time = match (threshold, meta.live_pid()) {
+                (Some(_), Some(pid)) => Some(
+                    tree(&processes, pid)
+                        .iter()
+                        .map(|(_, process)| process.cpu_time)
+                        .sum::<f64>(),
+                ),
+                _ => None,
+            };
+            let usage = match (meta.cpu_sample, cpu_time) {
+                (Some((at, before)), Some(cpu_time)) => {
+                    let elapsed = now.duration_since(at).as_secs_f64().max(0.001);
+                    Some(((cpu_time - before) / elapsed * 100.0).max(0.0) as u32)
+                }
+                _ => None,
+            };
+            meta.cpu_sample = cpu_time.map(|cpu_time| (now, cpu_time));
+            let busy = usage.filter(|usage| Some(*usage) >= threshold);
+            let changed = busy != meta.busy;
+            meta.busy = busy;
+            changed
+        };
+        if changed {
+            term.refresh_label(terminal);
+        }
+    }
+}
@@ -0,0 +1,61 @@
+// for screen sharing: while on, text matching the redact patterns and the
+// built-in ones (emails, ip addresses, common api tokens) is painted over.
+// Only the screen is covered, copying and the scrollback still have it.
+
+use gtk::cairo;
+use gtk::prelude::*;
+use regex::Regex;
+use vte::TerminalExt;
+
+use crate::{hacks, marks, Term, TermImpl};
+
+const EMAIL: &str = r"[\w.+-]+@[\w-]+(\.[\w-]+)+";
+const IPV4: &str = r"\b\d{1,3}(\.\d{1,3}){3}\b";
+const IPV6: &str = r"\b([0-9a-fA-F]{1,4}:){7}[0-9a-fA-F]{1,4}\b|\b([0-9a-fA-F]{1,4}:){1,7}:([0-9a-fA-F]{1,4}:?){1,7}\b";
+// github, openai style, aws access keys, slack and jwts
+const TOKEN: &str = r"\b(gh[pousr]_[A-Za-z0-9]{36,}|sk-[A-Za-z0-9_-]{20,}|AKIA[0-9A-Z]{16}|xox[abprs]-[A-Za-z0-9-]{10,}|eyJ[\w-]+\.[\w-]+\.[\w-]+)";
+
+pub fn builtin() -> Vec<Regex> {
+    [EMAIL, IPV4, IPV6, TOKEN]
+        .iter()
+        .map(|pattern| Regex::new(pattern).unwrap())
+        .collect()
+}
+
+// for this session, reloading the config goes back to its setting
+pub fn toggle(term: &Term) {
+    let imp = TermImpl::from_instance(term);
+    {
+        let mut config = imp.config.borrow_mut();
+        config.redact = !config.redact;
+    }
+    for terminal in imp.tabs.borrow().terminals() {
+        terminal.queue_draw();
+    }
+}
+
+// solid blocks over every match on screen
+pub fn draw(ctx: &cairo::Context, terminal: &vte::Terminal, patterns: &[Regex], color: gdk::RGBA) {
+    let padding = terminal.style_context().padding(terminal.state_flags());
+    let (width, height) = (terminal.char_width() as f64, terminal.char_height() as f64);
+    ctx.set_source_rgba(color.red, color.green, color.blue, 1.0);
+    let (top, rows) = marks::visible_rows(terminal);
+    for row in top..top + rows {
+        let (y, text) = match (marks::row_y(terminal, row), hacks::text_row(terminal, row)) {
+            (Some(y), Some(text)) => (y, text),
+            _ => continue,
+        };
+        for found in patterns.iter().flat_map(|pattern| pattern.find_iter(&text)) {
+            // columns in chars, as search does
+            let start = text[..found.start()].chars().count();
+            let length = found.as_str().chars().count();
+            ctx.rectangle(
+                padding.left as f64 + start as f64 * width,
+                y,
+                length as f64 * width,
+                height,
+            );
+        }
+    }
+    let _ = ctx.fill();
+}
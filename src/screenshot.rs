@@ -0,0 +1,78 @@
+// "Screenshot": the focused pane, its whole tab or the tabs with the tab bar,
+// as screenshot_area says, drawn offscreen into a png in the pictures
+// directory and put on the clipboard
+
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Error};
+use gtk::cairo;
+use gtk::prelude::*;
+use gtk::subclass::prelude::*;
+
+use crate::config::ScreenshotArea;
+use crate::{split, Term, TermImpl};
+
+fn render(widget: &gtk::Widget) -> Result<cairo::ImageSurface, Error> {
+    let (width, height) = (widget.allocated_width(), widget.allocated_height());
+    if width <= 0 || height <= 0 {
+        return Err(anyhow!("nothing to take a screenshot of"));
+    }
+    let surface = cairo::ImageSurface::create(cairo::Format::ARgb32, width, height)?;
+    {
+        let ctx = cairo::Context::new(&surface)?;
+        widget.draw(&ctx);
+    }
+    Ok(surface)
+}
+
+fn target(term: &Term, area: ScreenshotArea) -> Option<gtk::Widget> {
+    match area {
+        ScreenshotArea::Terminal => term.active_terminal().map(|terminal| terminal.upcast()),
+        ScreenshotArea::Tab => {
+            let terminal = term.active_terminal()?;
+            split::page(&terminal)
+        }
+        ScreenshotArea::Window => Some(term.notebook().upcast()),
+    }
+}
+
+fn path() -> PathBuf {
+    let dir = glib::user_special_dir(glib::UserDirectory::Pictures).unwrap_or_else(glib::home_dir);
+    let time = glib::DateTime::now_local()
+        .and_then(|now| now.format("%Y%m%d-%H%M%S"))
+        .map(|time| time.to_string())
+        .unwrap_or_default();
+    dir.join(format!("pterm-{}.png", time))
+}
+
+fn take(term: &Term) -> Result<PathBuf, Error> {
+    let area = TermImpl::from_instance(term)
+        .config
+        .borrow()
+        .screenshot_area;
+    let widget = target(term, area).ok_or_else(|| anyhow!("no terminal"))?;
+    let surface = render(&widget)?;
+    let path = path();
+    let mut file = std::fs::File::create(&path)?;
+    surface.write_to_png(&mut file)?;
+    let (width, height) = (surface.width(), surface.height());
+    if let Some(pixbuf) = gdk::pixbuf_get_from_surface(&surface, 0, 0, width, height) {
+        gtk::Clipboard::get(&gdk::SELECTION_CLIPBOARD).set_image(&pixbuf);
+    }
+    Ok(path)
+}
+
+pub fn screenshot(term: &Term) {
+    match take(term) {
+        Ok(path) => {
+            log::info!("saved a screenshot to {}", path.display());
+            let message = format!("Screenshot saved to {} and copied", path.display());
+            let banner = term.banner(gtk::MessageType::Info, &message);
+            banner.connect_response(|banner, _| {
+                banner.hide();
+                banner.destroy();
+            });
+        }
+        Err(err) => term.show_warning(&format!("can't take a screenshot: {}", err)),
+    }
+}
@@ -0,0 +1,54 @@
+// vte keeps only the last rows of the scrollback in memory and writes the
+// rest, compressed and encrypted, to files it deletes as soon as they're
+// open, in g_get_tmp_dir(). That's often a tmpfs, so a long scrollback ends
+// up in memory after all; scrollback_storage = "disk" points vte at the
+// cache directory instead. Only pterm's own TMPDIR changes, tabs get the one
+// pterm was started with.
+
+use std::cell::RefCell;
+use std::path::PathBuf;
+
+use anyhow::Error;
+
+use crate::config::ScrollbackStorage;
+
+const DIR: &str = "scrollback";
+
+thread_local! {
+    // TMPDIR=… for the children, or a bare TMPDIR when pterm was started
+    // without one; set with "disk"
+    static CHILD_TMPDIR: RefCell<Option<String>> = RefCell::new(None);
+}
+
+fn dir() -> Result<PathBuf, Error> {
+    let dirs = xdg::BaseDirectories::with_prefix("pterm")?;
+    Ok(dirs.create_cache_directory(DIR)?)
+}
+
+// before anything asks glib where the temporary directory is, it doesn't
+// look again
+pub fn set_storage(storage: ScrollbackStorage) {
+    if storage == ScrollbackStorage::Memory {
+        return;
+    }
+    let dir = match dir() {
+        Ok(dir) => dir,
+        Err(err) => {
+            log::warn!("no directory for the scrollback: {}", err);
+            return;
+        }
+    };
+    // vte leaves out the variables given without a value when it merges the
+    // tab's environment into ours
+    let child = match std::env::var("TMPDIR") {
+        Ok(original) => format!("TMPDIR={}", original),
+        Err(_) => "TMPDIR".to_string(),
+    };
+    CHILD_TMPDIR.with(|tmpdir| *tmpdir.borrow_mut() = Some(child));
+    std::env::set_var("TMPDIR", &dir);
+}
+
+// to add to the environment of a tab's command
+pub fn child_env() -> Option<String> {
+    CHILD_TMPDIR.with(|tmpdir| tmpdir.borrow().clone())
+}
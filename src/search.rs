@@ -0,0 +1,383 @@
+// find bar under the tabs: every match in the scrollback of the active
+// terminal is looked up at once and painted over the text by marks::draw
+
+use std::cell::{Cell, RefCell};
+
+use gtk::cairo;
+use gtk::prelude::*;
+use gtk::subclass::prelude::*;
+use regex::Regex;
+use vte::TerminalExt;
+
+use crate::actions::Action;
+use crate::tabs::Meta;
+use crate::{hacks, marks, Term, TermImpl};
+
+const MAX_MATCHES: usize = 10_000;
+// queries remembered for Up/Down in the entry, for as long as the window lives
+const MAX_HISTORY: usize = 50;
+// typing waits this long for the next key before going through the scrollback
+const TYPING_DELAY: std::time::Duration = std::time::Duration::from_millis(200);
+
+// columns are counted in chars, wide characters throw them off
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Match {
+    pub row: i64,
+    pub start: i64,
+    // exclusive
+    pub end: i64,
+}
+
+impl Match {
+    fn position(&self) -> (i64, i64) {
+        (self.row, self.start)
+    }
+}
+
+pub struct SearchBar {
+    pub bar: gtk::SearchBar,
+    pub entry: gtk::SearchEntry,
+    counter: gtk::Label,
+    whole_word: gtk::CheckButton,
+    // top of the screen when the bar was opened, typing searches from there
+    origin: Cell<Option<(i64, i64)>>,
+    // oldest first
+    history: RefCell<Vec<String>>,
+    history_index: Cell<Option<usize>>,
+    // the search typing has started but not yet run
+    pending: Cell<Option<glib::SourceId>>,
+}
+
+impl Default for SearchBar {
+    fn default() -> Self {
+        let entry = gtk::SearchEntry::builder().width_chars(30).build();
+        let counter = gtk::Label::new(None);
+        let whole_word = gtk::CheckButton::with_label("Whole word");
+        let previous = gtk::Button::from_icon_name(Some("go-up-symbolic"), gtk::IconSize::Button);
+        previous.set_action_name(Some(&Action::FindPrevious.detailed_name()));
+        let next = gtk::Button::from_icon_name(Some("go-down-symbolic"), gtk::IconSize::Button);
+        next.set_action_name(Some(&Action::FindNext.detailed_name()));
+
+        let content = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+        content.pack_start(&entry, true, true, 0);
+        content.pack_start(&counter, false, false, 0);
+        content.pack_start(&whole_word, false, false, 0);
+        content.pack_start(&previous, false, false, 0);
+        content.pack_start(&next, false, false, 0);
+
+        let bar = gtk::SearchBar::new();
+        bar.add(&content);
+        bar.connect_entry(&entry);
+        bar.set_show_close_button(true);
+        SearchBar {
+            bar,
+            entry,
+            counter,
+            whole_word,
+            origin: Cell::new(None),
+            history: Default::default(),
+            history_index: Cell::new(None),
+            pending: Cell::new(None),
+        }
+    }
+}
+
+impl SearchBar {
+    fn remember(&self) {
+        let query = self.entry.text().to_string();
+        self.history_index.set(None);
+        if query.is_empty() {
+            return;
+        }
+        let mut history = self.history.borrow_mut();
+        history.retain(|old| *old != query);
+        if history.len() >= MAX_HISTORY {
+            history.remove(0);
+        }
+        history.push(query);
+    }
+
+    // Up goes to older queries, Down back towards an empty entry
+    fn recall(&self, older: bool) {
+        let history = self.history.borrow();
+        let index = match (self.history_index.get(), older) {
+            (None, true) => history.len().checked_sub(1),
+            (Some(index), true) => Some(index.saturating_sub(1)),
+            (Some(index), false) if index + 1 < history.len() => Some(index + 1),
+            (_, false) => None,
+        };
+        self.history_index.set(index);
+        let text = index.map(|index| history[index].as_str()).unwrap_or("");
+        self.entry.set_text(text);
+        self.entry.set_position(-1);
+    }
+}
+
+// lowercase queries match any case
+fn regex(query: &str, whole_word: bool) -> Option<Regex> {
+    if query.is_empty() {
+        return None;
+    }
+    let flags = if query.chars().any(char::is_uppercase) {
+        ""
+    } else {
+        "(?i)"
+    };
+    let pattern = regex::escape(query);
+    let pattern = if whole_word {
+        format!(r"\b{}\b", pattern)
+    } else {
+        pattern
+    };
+    Regex::new(&format!("{}{}", flags, pattern)).ok()
+}
+
+// the whole scrollback comes in one piece, a wrapped line as one line of it
+// taking up as many full rows as its length needs
+fn scan(terminal: &vte::Terminal, regex: &Regex) -> Vec<Match> {
+    let adjustment = match terminal.vadjustment() {
+        Some(adjustment) => adjustment,
+        None => return Vec::new(),
+    };
+    let (lower, upper) = (adjustment.lower() as i64, adjustment.upper() as i64);
+    let text = match hacks::text_rows(terminal, lower, upper - 1) {
+        Some(text) => text,
+        None => return Vec::new(),
+    };
+    let columns = terminal.column_count().max(1);
+    let mut matches = Vec::new();
+    let mut row = lower;
+    for line in text.split('\n') {
+        if row >= upper {
+            break;
+        }
+        for found in regex
+            .find_iter(line)
+            .filter(|found| !found.as_str().is_empty())
+        {
+            let offset = line[..found.start()].chars().count() as i64;
+            let start = offset % columns;
+            let end = (start + found.as_str().chars().count() as i64).min(columns);
+            matches.push(Match {
+                row: row + offset / columns,
+                start,
+                end,
+            });
+            if matches.len() >= MAX_MATCHES {
+                return matches;
+            }
+        }
+        let length = line.chars().count() as i64;
+        row += ((length + columns - 1) / columns).max(1);
+    }
+    matches
+}
+
+// find(term, None) once typing stops for a moment
+fn find_soon(term: &Term) {
+    let imp = TermImpl::from_instance(term);
+    if let Some(source) = imp.search.pending.take() {
+        glib::source_remove(source);
+    }
+    let source = glib::timeout_add_local_once(
+        TYPING_DELAY,
+        glib::clone!(@weak term => move || {
+            TermImpl::from_instance(&term).search.pending.take();
+            find(&term, None);
+        }),
+    );
+    imp.search.pending.set(Some(source));
+}
+
+// looks the query up again in the active terminal and scrolls to the next or
+// previous match after the current one; without a direction, as while
+// typing, to the first one below where the search started
+pub fn find(term: &Term, forward: Option<bool>) {
+    let imp = TermImpl::from_instance(term);
+    if let Some(source) = imp.search.pending.take() {
+        glib::source_remove(source);
+    }
+    let terminal = match term.active_terminal() {
+        Some(terminal) => terminal,
+        None => return,
+    };
+    let query = imp.search.entry.text();
+    let matches = regex(&query, imp.search.whole_word.is_active())
+        .map(|regex| scan(&terminal, &regex))
+        .unwrap_or_default();
+
+    let (top, rows) = marks::visible_rows(&terminal);
+    let mut tabs = imp.tabs.borrow_mut();
+    let meta = match tabs.by_terminal_mut(&terminal) {
+        Some(meta) => meta,
+        None => return,
+    };
+    let previous = meta
+        .search_current
+        .and_then(|index| meta.search_matches.get(index))
+        .map(Match::position);
+    let current = match forward {
+        None => {
+            let origin = imp.search.origin.get().unwrap_or((top, 0));
+            matches.iter().position(|found| found.position() >= origin)
+        }
+        Some(true) => {
+            let after = previous.unwrap_or((top, -1));
+            matches
+                .iter()
+                .position(|found| found.position() > after)
+                .or_else(|| (!matches.is_empty()).then(|| 0))
+        }
+        Some(false) => {
+            let before = previous.unwrap_or((top + rows, 0));
+            matches.iter().rposition(|found| found.position() < before)
+        }
+    }
+    .or_else(|| matches.len().checked_sub(1));
+
+    imp.search.counter.set_text(&match current {
+        Some(index) if matches.len() >= MAX_MATCHES => {
+            format!("{}/{}+", index + 1, matches.len())
+        }
+        Some(index) => format!("{}/{}", index + 1, matches.len()),
+        None if query.is_empty() => String::new(),
+        None => "no matches".to_string(),
+    });
+    let row = current.map(|index| matches[index].row);
+    meta.search_matches = matches;
+    meta.search_current = current;
+    drop(tabs);
+
+    if let (Some(row), Some(adjustment)) = (row, terminal.vadjustment()) {
+        if row < top || row >= top + rows {
+            adjustment.set_value((row - rows / 2).max(adjustment.lower() as i64) as f64);
+        }
+    }
+    terminal.queue_draw();
+}
+
+fn clear(term: &Term) {
+    let imp = TermImpl::from_instance(term);
+    let terminals: Vec<vte::Terminal> = imp.tabs.borrow().terminals().cloned().collect();
+    for terminal in terminals {
+        if let Some(meta) = imp.tabs.borrow_mut().by_terminal_mut(&terminal) {
+            meta.search_matches.clear();
+            meta.search_current = None;
+        }
+        terminal.queue_draw();
+    }
+    imp.search.counter.set_text("");
+}
+
+pub fn show(term: &Term) {
+    let imp = TermImpl::from_instance(term);
+    if !imp.search.bar.is_search_mode() {
+        let top = term
+            .active_terminal()
+            .map(|terminal| (marks::visible_rows(&terminal).0, 0));
+        imp.search.origin.set(top);
+    }
+    imp.search.bar.set_search_mode(true);
+    imp.search.entry.grab_focus();
+}
+
+// relies on the selection being owned by the terminal, like search_selection
+pub fn find_selection(term: &Term) {
+    let clipboard = gtk::Clipboard::get(&gdk::SELECTION_PRIMARY);
+    clipboard.request_text(glib::clone!(@weak term => move |_, text| {
+        let text = match text {
+            Some(text) if !text.trim().is_empty() => text.trim().to_string(),
+            _ => return,
+        };
+        find_text(&term, &text, None);
+    }));
+}
+
+// searches for `text` from `origin`, the top of the screen if None
+pub fn find_text(term: &Term, text: &str, origin: Option<(i64, i64)>) {
+    let imp = TermImpl::from_instance(term);
+    show(term);
+    if origin.is_some() {
+        imp.search.origin.set(origin);
+    }
+    // one line is all the entry can take
+    imp.search
+        .entry
+        .set_text(text.trim().lines().next().unwrap_or_default());
+    find(term, None);
+}
+
+pub fn connect(term: &Term) {
+    let imp = TermImpl::from_instance(term);
+    let entry = &imp.search.entry;
+
+    entry.connect_search_changed(glib::clone!(@weak term => move |_| find_soon(&term)));
+    entry.connect_activate(glib::clone!(@weak term => move |_| {
+        TermImpl::from_instance(&term).search.remember();
+        find(&term, Some(true));
+    }));
+    entry.connect_next_match(glib::clone!(@weak term => move |_| find(&term, Some(true))));
+    entry.connect_previous_match(glib::clone!(@weak term => move |_| find(&term, Some(false))));
+    entry.connect_key_press_event(
+        glib::clone!(@weak term => @default-return Inhibit(false), move |_, event| {
+            let older = match event.keyval() {
+                gdk::keys::constants::Up => true,
+                gdk::keys::constants::Down => false,
+                _ => return Inhibit(false),
+            };
+            TermImpl::from_instance(&term).search.recall(older);
+            Inhibit(true)
+        }),
+    );
+    imp.search
+        .whole_word
+        .connect_toggled(glib::clone!(@weak term => move |_| find(&term, None)));
+    entry.connect_stop_search(glib::clone!(@weak term => move |_| {
+        TermImpl::from_instance(&term).search.bar.set_search_mode(false);
+    }));
+
+    imp.search
+        .bar
+        .connect_search_mode_enabled_notify(glib::clone!(@weak term => move |bar| {
+            if bar.is_search_mode() {
+                return;
+            }
+            let imp = TermImpl::from_instance(&term);
+            imp.search.remember();
+            imp.search.origin.set(None);
+            clear(&term);
+            if let Some(terminal) = term.active_terminal() {
+                terminal.grab_focus();
+            }
+        }));
+}
+
+// every match on screen, the current one stronger
+pub fn draw(ctx: &cairo::Context, terminal: &vte::Terminal, meta: &Meta, color: gdk::RGBA) {
+    let (top, rows) = marks::visible_rows(terminal);
+    let matches = &meta.search_matches;
+    let first = matches.partition_point(|found| found.row < top);
+    let last = matches.partition_point(|found| found.row < top + rows);
+
+    let padding = terminal.style_context().padding(terminal.state_flags());
+    let (width, height) = (terminal.char_width() as f64, terminal.char_height() as f64);
+    for (index, found) in matches.iter().enumerate().take(last).skip(first) {
+        let y = match marks::row_y(terminal, found.row) {
+            Some(y) => y,
+            None => continue,
+        };
+        let alpha = if meta.search_current == Some(index) {
+            0.8
+        } else {
+            0.35
+        };
+        ctx.set_source_rgba(color.red, color.green, color.blue, alpha);
+        ctx.rectangle(
+            padding.left as f64 + found.start as f64 * width,
+            y,
+            (found.end - found.start) as f64 * width,
+            height,
+        );
+        let _ = ctx.fill();
+    }
+}
@@ -0,0 +1,136 @@
+// "Type Secret…": a password manager is asked for a secret by name and its
+// answer is typed into the terminal, so it never goes through the clipboard.
+// Each backend is a command line tool, its first line of output is the secret.
+
+use gtk::prelude::*;
+use vte::TerminalExt;
+
+use crate::config::SecretBackend;
+use crate::{Term, TermImpl};
+
+pub trait Backend {
+    // the command that prints the secret `name`
+    fn command(&self, name: &str) -> Vec<String>;
+    // what to type, out of everything the command printed
+    fn secret(&self, output: &str) -> Option<String> {
+        output.lines().next().map(String::from)
+    }
+}
+
+// libsecret's secret-tool; a single word is looked up as the attribute
+// pterm=<word> (`secret-tool store --label=<label> pterm <word>` saves one),
+// otherwise the name is attribute and value pairs, "service github user me"
+struct SecretService;
+
+impl Backend for SecretService {
+    fn command(&self, name: &str) -> Vec<String> {
+        let mut command = vec!["secret-tool".to_string(), "lookup".to_string()];
+        let words: Vec<&str> = name.split_whitespace().collect();
+        if words.len() == 1 {
+            command.push("pterm".to_string());
+        }
+        command.extend(words.into_iter().map(String::from));
+        command
+    }
+}
+
+struct Pass;
+
+impl Backend for Pass {
+    fn command(&self, name: &str) -> Vec<String> {
+        vec!["pass".to_string(), "show".to_string(), name.to_string()]
+    }
+}
+
+struct Rbw;
+
+impl Backend for Rbw {
+    fn command(&self, name: &str) -> Vec<String> {
+        vec!["rbw".to_string(), "get".to_string(), name.to_string()]
+    }
+}
+
+fn backend(kind: SecretBackend) -> Box<dyn Backend> {
+    match kind {
+        SecretBackend::SecretService => Box::new(SecretService),
+        SecretBackend::Pass => Box::new(Pass),
+        SecretBackend::Rbw => Box::new(Rbw),
+    }
+}
+
+pub fn autotype(term: &Term) {
+    let terminal = match term.active_terminal() {
+        Some(terminal) => terminal,
+        None => return,
+    };
+    let kind = match TermImpl::from_instance(term).config.borrow().secret_backend {
+        Some(kind) => kind,
+        None => {
+            term.show_warning("set secret_backend in the config to type secrets");
+            return;
+        }
+    };
+    let dialog = gtk::Dialog::with_buttons(
+        Some("Type Secret"),
+        Some(term),
+        gtk::DialogFlags::MODAL | gtk::DialogFlags::DESTROY_WITH_PARENT,
+        &[
+            ("Cancel", gtk::ResponseType::Cancel),
+            ("Type", gtk::ResponseType::Ok),
+        ],
+    );
+    dialog.set_default_response(gtk::ResponseType::Ok);
+    let entry = gtk::Entry::builder()
+        .placeholder_text("secret name")
+        .activates_default(true)
+        .build();
+    dialog.content_area().pack_start(&entry, false, false, 6);
+
+    dialog.connect_response(
+        glib::clone!(@weak term, @weak terminal, @weak entry => move |dialog, response| {
+            let name = entry.text().trim().to_string();
+            dialog.close();
+            terminal.grab_focus();
+            if response == gtk::ResponseType::Ok && !name.is_empty() {
+                fetch(&term, &terminal, backend(kind), &name);
+            }
+        }),
+    );
+    dialog.show_all();
+}
+
+fn fetch(term: &Term, terminal: &vte::Terminal, backend: Box<dyn Backend>, name: &str) {
+    let argv = backend.command(name);
+    let args: Vec<&std::ffi::OsStr> = argv.iter().map(|arg| arg.as_ref()).collect();
+    let flags = gio::SubprocessFlags::STDOUT_PIPE | gio::SubprocessFlags::STDERR_PIPE;
+    let process = match gio::Subprocess::newv(&args, flags) {
+        Ok(process) => process,
+        Err(err) => {
+            term.show_warning(&format!("can't run {}: {}", argv[0], err));
+            return;
+        }
+    };
+    // the secret itself is never logged
+    process.communicate_utf8_async(
+        None,
+        None::<&gio::Cancellable>,
+        glib::clone!(@weak term, @weak terminal, @strong process => move |result| {
+            let (stdout, stderr) = match result {
+                Ok(output) => output,
+                Err(err) => {
+                    term.show_warning(&format!("{} failed: {}", argv[0], err));
+                    return;
+                }
+            };
+            if !process.is_successful() {
+                let stderr = stderr.map(|stderr| stderr.trim().to_string()).unwrap_or_default();
+                term.show_warning(&format!("{} failed: {}", argv[0], stderr));
+                return;
+            }
+            match stdout.as_deref().and_then(|stdout| backend.secret(stdout)) {
+                Some(secret) if !secret.is_empty() => terminal.feed_child(secret.as_bytes()),
+                _ => term.show_warning(&format!("{} printed no secret", argv[0])),
+            }
+        }),
+    );
+}
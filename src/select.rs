@@ -0,0 +1,219 @@
+// selecting scrollback with the keyboard: vte has no api to set its own
+// selection, so this one is ours, painted by marks::draw and put in PRIMARY
+// like a mouse selection would be
+
+use gtk::cairo;
+use gtk::prelude::*;
+use gtk::subclass::prelude::*;
+use vte::TerminalExt;
+
+use crate::tabs::Meta;
+use crate::{hacks, marks, search, Term, TermImpl};
+
+// row and column of a cell
+pub type Position = (i64, i64);
+
+// `head` is the end that moves
+#[derive(Debug, Clone, Copy)]
+pub struct Selection {
+    pub anchor: Position,
+    pub head: Position,
+}
+
+impl Selection {
+    fn bounds(&self) -> (Position, Position) {
+        (self.anchor.min(self.head), self.anchor.max(self.head))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+fn text(terminal: &vte::Terminal, selection: &Selection) -> Option<String> {
+    let (start, end) = selection.bounds();
+    hacks::text_range(terminal, start, end)
+}
+
+// first and last cell of the keyboard selection
+pub fn bounds(term: &Term, terminal: &vte::Terminal) -> Option<(Position, Position)> {
+    selection(term, terminal).map(|selection| selection.bounds())
+}
+
+fn selection(term: &Term, terminal: &vte::Terminal) -> Option<Selection> {
+    let imp = TermImpl::from_instance(term);
+    let tabs = imp.tabs.borrow();
+    tabs.by_terminal(terminal)?.selection
+}
+
+fn set(term: &Term, terminal: &vte::Terminal, selection: Option<Selection>) {
+    let imp = TermImpl::from_instance(term);
+    if let Some(meta) = imp.tabs.borrow_mut().by_terminal_mut(terminal) {
+        meta.selection = selection;
+    }
+    if let Some(selection) = &selection {
+        if let Some(adjustment) = terminal.vadjustment() {
+            let (top, rows) = marks::visible_rows(terminal);
+            let row = selection.head.0;
+            if row < top {
+                adjustment.set_value(row as f64);
+            } else if row >= top + rows {
+                adjustment.set_value((row - rows + 1) as f64);
+            }
+        }
+        if let Some(text) = text(terminal, selection) {
+            gtk::Clipboard::get(&gdk::SELECTION_PRIMARY).set_text(&text);
+        }
+    }
+    terminal.queue_draw();
+}
+
+fn rows(terminal: &vte::Terminal) -> (i64, i64) {
+    match terminal.vadjustment() {
+        Some(adjustment) => (adjustment.lower() as i64, adjustment.upper() as i64 - 1),
+        None => (0, terminal.row_count() as i64 - 1),
+    }
+}
+
+// the line above the cursor, again to take in one more line above
+pub fn select_previous_line(term: &Term, terminal: &vte::Terminal) {
+    let (first, _) = rows(terminal);
+    let selection = match selection(term, terminal) {
+        Some(selection) => Selection {
+            anchor: selection.anchor,
+            head: ((selection.head.0 - 1).max(first), 0),
+        },
+        None => {
+            let row = (terminal.cursor_position().1 as i64 - 1).max(first);
+            Selection {
+                anchor: (row, terminal.column_count() as i64 - 1),
+                head: (row, 0),
+            }
+        }
+    };
+    set(term, terminal, Some(selection));
+}
+
+// starts at the cursor when nothing is selected
+pub fn extend(term: &Term, terminal: &vte::Terminal, direction: Direction) {
+    let (first, last) = rows(terminal);
+    let columns = terminal.column_count() as i64;
+    let mut selection = selection(term, terminal).unwrap_or_else(|| {
+        let (column, row) = terminal.cursor_position();
+        let cell = (row as i64, column as i64);
+        Selection {
+            anchor: cell,
+            head: cell,
+        }
+    });
+    let (row, column) = selection.head;
+    selection.head = match direction {
+        Direction::Up => ((row - 1).max(first), column),
+        Direction::Down => ((row + 1).min(last), column),
+        Direction::Left if column > 0 => (row, column - 1),
+        Direction::Left if row > first => (row - 1, columns - 1),
+        Direction::Right if column + 1 < columns => (row, column + 1),
+        Direction::Right if row < last => (row + 1, 0),
+        Direction::Left | Direction::Right => (row, column),
+    };
+    set(term, terminal, Some(selection));
+}
+
+pub fn clear(term: &Term, terminal: &vte::Terminal) {
+    set(term, terminal, None);
+}
+
+fn copy(term: &Term, terminal: &vte::Terminal) {
+    if let Some(text) = selection(term, terminal).and_then(|selection| text(terminal, &selection)) {
+        gtk::Clipboard::get(&gdk::SELECTION_CLIPBOARD).set_text(&text);
+    }
+}
+
+// the keyboard selection if there's one, otherwise whatever the mouse selected
+pub fn find_next_selected(term: &Term) {
+    let terminal = match term.active_terminal() {
+        Some(terminal) => terminal,
+        None => return,
+    };
+    match selection(term, &terminal) {
+        Some(selection) => {
+            let (start, _) = selection.bounds();
+            if let Some(text) = text(&terminal, &selection) {
+                // the search starts right after the selected occurrence
+                search::find_text(term, &text, Some((start.0, start.1 + 1)));
+            }
+        }
+        None => search::find_selection(term),
+    }
+}
+
+// while something is selected Shift+arrows extend it, Enter copies it and
+// Escape or any other key drops it; true if the key was used
+pub fn handle_key(term: &Term, event: &gdk::EventKey) -> bool {
+    let terminal = match term.active_terminal() {
+        Some(terminal) => terminal,
+        None => return false,
+    };
+    if selection(term, &terminal).is_none() {
+        return false;
+    }
+    let shift = event.state().contains(gdk::ModifierType::SHIFT_MASK);
+    let direction = match event.keyval() {
+        gdk::keys::constants::Up if shift => Direction::Up,
+        gdk::keys::constants::Down if shift => Direction::Down,
+        gdk::keys::constants::Left if shift => Direction::Left,
+        gdk::keys::constants::Right if shift => Direction::Right,
+        gdk::keys::constants::Return => {
+            copy(term, &terminal);
+            clear(term, &terminal);
+            return true;
+        }
+        gdk::keys::constants::Escape => {
+            clear(term, &terminal);
+            return true;
+        }
+        // modifiers on their own, e.g. on the way to Shift+arrow
+        _ if event.is_modifier() => return false,
+        _ => {
+            clear(term, &terminal);
+            return false;
+        }
+    };
+    extend(term, &terminal, direction);
+    true
+}
+
+pub fn draw(ctx: &cairo::Context, terminal: &vte::Terminal, meta: &Meta, color: gdk::RGBA) {
+    let ((start_row, start_column), (end_row, end_column)) = match &meta.selection {
+        Some(selection) => selection.bounds(),
+        None => return,
+    };
+    let (top, rows) = marks::visible_rows(terminal);
+    let padding = terminal.style_context().padding(terminal.state_flags());
+    let (width, height) = (terminal.char_width() as f64, terminal.char_height() as f64);
+    let columns = terminal.column_count() as i64;
+    ctx.set_source_rgba(color.red, color.green, color.blue, 0.35);
+    for row in start_row.max(top)..=end_row.min(top + rows - 1) {
+        let y = match marks::row_y(terminal, row) {
+            Some(y) => y,
+            None => continue,
+        };
+        let first = if row == start_row { start_column } else { 0 };
+        let last = if row == end_row {
+            end_column
+        } else {
+            columns - 1
+        };
+        ctx.rectangle(
+            padding.left as f64 + first as f64 * width,
+            y,
+            (last - first + 1) as f64 * width,
+            height,
+        );
+    }
+    let _ = ctx.fill();
+}
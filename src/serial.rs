@@ -0,0 +1,175 @@
+// `pterm serial <device>` opens a tab talking to a serial port. The tab runs
+// pterm itself as a small bridge (the hidden `--serial-bridge` with the same
+// arguments): it sets the device up, puts the pty in raw mode and copies
+// between the two, with Enter sent as the line ending the device wants.
+
+use std::io::Write;
+use std::path::PathBuf;
+
+use anyhow::{anyhow, bail, Error};
+
+use crate::platform;
+
+pub const BRIDGE_FLAG: &str = "--serial-bridge";
+const USAGE: &str = "usage: pterm serial <device> [--baud <rate>] \
+                     [--line-ending cr|lf|crlf] [--flow none|software|hardware]";
+const DEFAULT_BAUD: u32 = 115_200;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineEnding {
+    Cr,
+    Lf,
+    CrLf,
+}
+
+impl LineEnding {
+    fn bytes(self) -> &'static [u8] {
+        match self {
+            LineEnding::Cr => b"\r",
+            LineEnding::Lf => b"\n",
+            LineEnding::CrLf => b"\r\n",
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            LineEnding::Cr => "cr",
+            LineEnding::Lf => "lf",
+            LineEnding::CrLf => "crlf",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flow {
+    None,
+    // XON/XOFF
+    Software,
+    // RTS/CTS
+    Hardware,
+}
+
+impl Flow {
+    fn name(self) -> &'static str {
+        match self {
+            Flow::None => "none",
+            Flow::Software => "software",
+            Flow::Hardware => "hardware",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Port {
+    pub device: PathBuf,
+    pub baud: u32,
+    pub line_ending: LineEnding,
+    pub flow: Flow,
+}
+
+// everything after `serial`
+pub fn parse(args: &mut impl Iterator<Item = String>) -> Result<Port, Error> {
+    let mut device = None;
+    let mut baud = DEFAULT_BAUD;
+    let mut line_ending = LineEnding::Cr;
+    let mut flow = Flow::None;
+    while let Some(arg) = args.next() {
+        // --baud=9600 or --baud 9600
+        let (name, attached) = match arg.split_once('=') {
+            Some((name, value)) if name.starts_with("--") => {
+                (name.to_string(), Some(value.to_string()))
+            }
+            _ => (arg.clone(), None),
+        };
+        let mut value = || {
+            attached
+                .clone()
+                .or_else(|| args.next())
+                .ok_or_else(|| anyhow!("{} requires a value", name))
+        };
+        match name.as_str() {
+            "--baud" | "-b" => {
+                let rate = value()?;
+                baud = rate
+                    .parse()
+                    .map_err(|_| anyhow!("invalid baud rate {:?}", rate))?;
+            }
+            "--line-ending" => {
+                line_ending = match value()?.as_str() {
+                    "cr" => LineEnding::Cr,
+                    "lf" => LineEnding::Lf,
+                    "crlf" => LineEnding::CrLf,
+                    other => bail!("invalid line ending {:?}, cr, lf or crlf", other),
+                }
+            }
+            "--flow" => {
+                flow = match value()?.as_str() {
+                    "none" => Flow::None,
+                    "software" | "xonxoff" => Flow::Software,
+                    "hardware" | "rtscts" => Flow::Hardware,
+                    other => bail!(
+                        "invalid flow control {:?}, none, software or hardware",
+                        other
+                    ),
+                }
+            }
+            _ if device.is_none() && !arg.starts_with('-') => device = Some(PathBuf::from(arg)),
+            _ => bail!("unexpected argument {:?}\n{}", arg, USAGE),
+        }
+    }
+    match device {
+        Some(device) => Ok(Port {
+            device,
+            baud,
+            line_ending,
+            flow,
+        }),
+        None => bail!(USAGE),
+    }
+}
+
+impl Port {
+    // what the tab runs
+    pub fn command(&self) -> Result<Vec<String>, Error> {
+        let exe = std::env::current_exe()?;
+        Ok(vec![
+            exe.to_string_lossy().into_owned(),
+            BRIDGE_FLAG.to_string(),
+            self.device.to_string_lossy().into_owned(),
+            format!("--baud={}", self.baud),
+            format!("--line-ending={}", self.line_ending.name()),
+            format!("--flow={}", self.flow.name()),
+        ])
+    }
+}
+
+// runs in the tab until the device goes away or the tab is closed
+pub fn bridge(port: &Port) -> Result<(), Error> {
+    let device = platform::open_serial(&port.device, port.baud, port.flow)
+        .map_err(|err| anyhow!("can't open {}: {}", port.device.display(), err))?;
+    platform::raw_input(0)?;
+    let mut stdout = std::io::stdout();
+    // the tab title, then a line to say it's working since devices are quiet
+    write!(
+        stdout,
+        "\x1b]2;{} {}\x07connected to {} at {} baud\r\n",
+        port.device.display(),
+        port.baud,
+        port.device.display(),
+        port.baud
+    )?;
+    stdout.flush()?;
+    let ending = port.line_ending.bytes();
+    platform::relay(&device, |keys| {
+        let mut bytes = Vec::with_capacity(keys.len());
+        for &key in keys {
+            if key == b'\r' {
+                bytes.extend_from_slice(ending);
+            } else {
+                bytes.push(key);
+            }
+        }
+        bytes
+    })
+    .map_err(|err| anyhow!("{}: {}", port.device.display(), err))
+}
@@ -0,0 +1,202 @@
+// shell integration: the scripts in shell/ report prompts and commands as
+// OSC 6 (current file) uris like file://pterm/<seq>/<event>/<argument>, which
+// vte hands over as current-file-uri without interpreting them. The prompt
+// brings facts along, precmd/<status>/venv=…&branch=…&command=…, in one
+// sequence since vte only tells about the last uri of a batch of output; the
+// command line is there too as a fast command's preexec is easily lost.
+//
+// Any output can carry such a sequence, `cat` of a file or a remote host's
+// shell. Command lines only go into the history from a precmd that has the
+// token the tab's shell was started with in $PTERM_TOKEN, which the scripts
+// take out of the environment, and a seq above the last one recorded.
+
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+use gtk::prelude::*;
+use gtk::subclass::prelude::*;
+use vte::TerminalExt;
+
+use crate::{history, watch, Term, TermImpl};
+
+const PREFIX: &str = "file://pterm/";
+pub const TOKEN_VAR: &str = "PTERM_TOKEN";
+// how long "(took …)" stays in the tab title
+const DURATION_SHOWN: Duration = Duration::from_secs(10);
+// placeholders that are empty rather than left as they are when not known
+const KNOWN: &[&str] = &["venv", "branch", "aws_profile", "container"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    // the command line was entered and is about to run
+    Preexec(String),
+    // the prompt is back, with the exit status and the line of the last command
+    // if there was one, the token if the shell knows it, and the facts
+    Precmd(
+        i32,
+        Option<String>,
+        Option<String>,
+        BTreeMap<String, String>,
+    ),
+}
+
+// a new one for every shell started
+pub fn new_token() -> String {
+    format!(
+        "{:08x}{:08x}{:08x}{:08x}",
+        glib::random_int(),
+        glib::random_int(),
+        glib::random_int(),
+        glib::random_int()
+    )
+}
+
+// with the seq the event was sent with
+pub fn parse(uri: &str) -> Option<(u64, Event)> {
+    let mut parts = uri.strip_prefix(PREFIX)?.splitn(3, '/');
+    let seq = parts.next()?.parse().ok()?;
+    let event = parts.next()?;
+    let argument = parts.next().unwrap_or("");
+    let unescape = |s: &str| glib::uri_unescape_string(s, None::<&str>).map(|s| s.to_string());
+    let event = match event {
+        "preexec" => Event::Preexec(unescape(argument)?),
+        "precmd" => {
+            let (status, facts) = argument.split_once('/').unwrap_or((argument, ""));
+            let mut facts: BTreeMap<String, String> = facts
+                .split('&')
+                .filter_map(|fact| fact.split_once('='))
+                .filter_map(|(key, value)| Some((key.to_string(), unescape(value)?)))
+                .filter(|(_, value)| !value.is_empty())
+                .collect();
+            let command = facts.remove("command");
+            let token = facts.remove("token");
+            Event::Precmd(status.parse().ok()?, command, token, facts)
+        }
+        _ => return None,
+    };
+    Some((seq, event))
+}
+
+// whether a precmd with `seq` and `token` comes from the tab's own shell and
+// isn't one seen before; remembers `seq` if so
+fn trusted(term: &Term, terminal: &vte::Terminal, seq: u64, token: Option<&str>) -> bool {
+    let imp = TermImpl::from_instance(term);
+    let mut tabs = imp.tabs.borrow_mut();
+    let meta = match tabs.by_terminal_mut(terminal) {
+        Some(meta) => meta,
+        None => return false,
+    };
+    let fresh = meta.recorded_seq.map(|last| seq > last).unwrap_or(true);
+    if token.is_none() || token != meta.shell_token.as_deref() || !fresh {
+        return false;
+    }
+    meta.recorded_seq = Some(seq);
+    true
+}
+
+// {venv} and co in `template`
+pub fn fill(template: &str, placeholders: &BTreeMap<String, String>) -> String {
+    let mut text = template.to_string();
+    for (key, value) in placeholders {
+        text = text.replace(&format!("{{{}}}", key), value);
+    }
+    for key in KNOWN {
+        text = text.replace(&format!("{{{}}}", key), "");
+    }
+    text
+}
+
+// 45s, 3m12s, 1h05m
+pub fn human_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    match secs {
+        0..=59 => format!("{}s", secs),
+        60..=3599 => format!("{}m{:02}s", secs / 60, secs % 60),
+        _ => format!("{}h{:02}m", secs / 3600, secs % 3600 / 60),
+    }
+}
+
+// called on current-file-uri-changed
+pub fn handle(term: &Term, terminal: &vte::Terminal) {
+    let (seq, event) = match terminal.current_file_uri().and_then(|uri| parse(&uri)) {
+        Some(parsed) => parsed,
+        None => return,
+    };
+    let imp = TermImpl::from_instance(term);
+    match event {
+        Event::Preexec(command) => {
+            if let Some(meta) = imp.tabs.borrow_mut().by_terminal_mut(terminal) {
+                meta.command = Some((command, Instant::now()));
+                meta.took = None;
+            }
+        }
+        Event::Precmd(status, line, token, facts) => {
+            watch::set_progress(term, terminal, None);
+            if let Some(line) = line
+                .as_ref()
+                .filter(|_| trusted(term, terminal, seq, token.as_deref()))
+            {
+                let cwd = terminal
+                    .current_directory_uri()
+                    .and_then(|uri| gio::File::for_uri(&uri).path())
+                    .or_else(|| term.get_terminal_cwd(terminal));
+                history::record(line, cwd.as_deref());
+            }
+            let changed = match imp.tabs.borrow_mut().by_terminal_mut(terminal) {
+                Some(meta) if meta.facts != facts => {
+                    meta.facts = facts;
+                    true
+                }
+                _ => false,
+            };
+            if changed {
+                term.refresh_label(terminal);
+                // the badge
+                terminal.queue_draw();
+            }
+            let threshold = imp.config.borrow().long_command;
+            let finished = imp
+                .tabs
+                .borrow_mut()
+                .by_terminal_mut(terminal)
+                .and_then(|meta| meta.command.take());
+            let (command, started) = match finished {
+                Some(finished) => finished,
+                None => return,
+            };
+            let took = started.elapsed();
+            if threshold.is_zero() || took < threshold {
+                return;
+            }
+            if let Some(meta) = imp.tabs.borrow_mut().by_terminal_mut(terminal) {
+                meta.took = Some(took);
+            }
+            term.refresh_label(terminal);
+            glib::timeout_add_local_once(
+                DURATION_SHOWN,
+                glib::clone!(@weak term, @weak terminal => move || {
+                    let imp = TermImpl::from_instance(&term);
+                    if let Some(meta) = imp.tabs.borrow_mut().by_terminal_mut(&terminal) {
+                        if meta.took == Some(took) {
+                            meta.took = None;
+                        }
+                    }
+                    term.refresh_label(&terminal);
+                }),
+            );
+
+            let visible = term.is_active() && term.active_terminal().as_ref() == Some(terminal);
+            if !visible {
+                let summary = if status == 0 {
+                    "Command finished"
+                } else {
+                    "Command failed"
+                };
+                // bash's preexec only has the first command of a pipeline
+                let command = line.unwrap_or(command);
+                let body = format!("{} took {}", command, human_duration(took));
+                term.alert(terminal, summary, &body);
+            }
+        }
+    }
+}
@@ -0,0 +1,48 @@
+// [snippets] from the config in a picker; the one chosen is typed into the
+// terminal, and with a {cursor} in it the cursor is walked back there with
+// Left presses, which is as far as a shell's line editor will take it
+
+use gtk::prelude::*;
+use vte::TerminalExt;
+
+use crate::{picker, Term, TermImpl};
+
+pub const CURSOR: &str = "{cursor}";
+const LEFT: &[u8] = b"\x1b[D";
+
+pub fn insert(terminal: &vte::Terminal, text: &str) {
+    match text.split_once(CURSOR) {
+        Some((before, after)) => {
+            terminal.feed_child(format!("{}{}", before, after).as_bytes());
+            terminal.feed_child(&LEFT.repeat(after.chars().count()));
+        }
+        None => terminal.feed_child(text.as_bytes()),
+    }
+}
+
+pub fn show(term: &Term) {
+    let terminal = match term.active_terminal() {
+        Some(terminal) => terminal,
+        None => return,
+    };
+    let snippets = TermImpl::from_instance(term)
+        .config
+        .borrow()
+        .snippets
+        .clone();
+    if snippets.is_empty() {
+        term.show_warning("no [snippets] in the config");
+        return;
+    }
+    let items = snippets
+        .iter()
+        .map(|(name, text)| picker::Item {
+            label: name.clone(),
+            detail: Some(text.replace(CURSOR, "").replace('\n', " ")),
+        })
+        .collect();
+    picker::show(term, "Insert Snippet", items, move |index| {
+        insert(&terminal, &snippets[index].1);
+        terminal.grab_focus();
+    });
+}
@@ -0,0 +1,208 @@
+// split panes: every notebook page is a box holding either a terminal or a
+// tree of gtk::Paned with terminals as leaves
+
+use std::cell::Cell;
+use std::rc::Rc;
+
+use gtk::prelude::*;
+
+// `root` is a terminal or a paned
+pub fn new_page(root: &impl IsA<gtk::Widget>) -> gtk::Box {
+    let page = gtk::Box::new(gtk::Orientation::Horizontal, 0);
+    page.pack_start(root, true, true, 0);
+    page
+}
+
+// the notebook page `widget` is on
+pub fn page(widget: &impl IsA<gtk::Widget>) -> Option<gtk::Widget> {
+    let mut widget = widget.clone().upcast::<gtk::Widget>();
+    loop {
+        let parent = widget.parent()?;
+        if parent.is::<gtk::Notebook>() {
+            return Some(widget);
+        }
+        widget = parent;
+    }
+}
+
+// leaves of the tree, left to right and top to bottom
+pub fn terminals(widget: &gtk::Widget) -> Vec<vte::Terminal> {
+    if let Some(terminal) = widget.downcast_ref::<vte::Terminal>() {
+        return vec![terminal.clone()];
+    }
+    match widget.downcast_ref::<gtk::Container>() {
+        Some(container) => container.children().iter().flat_map(terminals).collect(),
+        None => Vec::new(),
+    }
+}
+
+fn replace(parent: &gtk::Widget, old: &gtk::Widget, new: &gtk::Widget) {
+    if let Some(paned) = parent.downcast_ref::<gtk::Paned>() {
+        let first = paned.child1().as_ref() == Some(old);
+        paned.remove(old);
+        if first {
+            paned.pack1(new, true, false);
+        } else {
+            paned.pack2(new, true, false);
+        }
+    } else if let Some(page) = parent.downcast_ref::<gtk::Box>() {
+        page.remove(old);
+        page.pack_start(new, true, true, 0);
+    }
+}
+
+// puts `new` next to `terminal` in the space `terminal` had
+pub fn split(terminal: &vte::Terminal, new: &vte::Terminal, orientation: gtk::Orientation) {
+    let parent = match terminal.parent() {
+        Some(parent) => parent,
+        None => return,
+    };
+    let paned = gtk::Paned::new(orientation);
+    replace(&parent, terminal.upcast_ref(), paned.upcast_ref());
+    // hidden when it's the root of a locked tab, it stays so
+    let visible = terminal.is_visible();
+    paned.pack1(terminal, true, false);
+    paned.pack2(new, true, false);
+    terminal.show();
+    new.show();
+    paned.set_visible(visible);
+}
+
+// takes `terminal` out of its split, the other side gets the space;
+// false if it's the only terminal on the page
+pub fn unsplit(terminal: &vte::Terminal) -> bool {
+    let paned = match terminal
+        .parent()
+        .and_then(|parent| parent.downcast::<gtk::Paned>().ok())
+    {
+        Some(paned) => paned,
+        None => return false,
+    };
+    let sibling = sibling(&paned, terminal.upcast_ref());
+    paned.remove(terminal);
+    if let (Some(sibling), Some(parent)) = (sibling, paned.parent()) {
+        paned.remove(&sibling);
+        replace(&parent, paned.upcast_ref(), &sibling);
+    }
+    true
+}
+
+fn sibling(paned: &gtk::Paned, child: &gtk::Widget) -> Option<gtk::Widget> {
+    if paned.child1().as_ref() == Some(child) {
+        paned.child2()
+    } else {
+        paned.child1()
+    }
+}
+
+// hides everything on the page but `terminal`, a paned with one visible
+// child gives it all the space; returns what was hidden
+pub fn zoom(terminal: &vte::Terminal) -> Vec<gtk::Widget> {
+    let mut hidden = Vec::new();
+    let mut widget = terminal.clone().upcast::<gtk::Widget>();
+    while let Some(paned) = widget
+        .parent()
+        .and_then(|parent| parent.downcast::<gtk::Paned>().ok())
+    {
+        if let Some(sibling) = sibling(&paned, &widget).filter(|sibling| sibling.is_visible()) {
+            sibling.hide();
+            hidden.push(sibling);
+        }
+        widget = paned.upcast();
+    }
+    hidden
+}
+
+// moves the nearest divider with the given orientation by `delta` pixels
+pub fn resize(terminal: &vte::Terminal, orientation: gtk::Orientation, delta: i32) {
+    let mut widget = terminal.clone().upcast::<gtk::Widget>();
+    while let Some(parent) = widget.parent() {
+        if let Some(paned) = parent.downcast_ref::<gtk::Paned>() {
+            if paned.orientation() == orientation {
+                paned.set_position((paned.position() + delta).max(0));
+                return;
+            }
+        }
+        widget = parent;
+    }
+}
+
+fn from_bottom(adjustment: &gtk::Adjustment) -> f64 {
+    adjustment.upper() - adjustment.page_size() - adjustment.value()
+}
+
+fn has_scrollback(adjustment: &gtk::Adjustment) -> bool {
+    adjustment.upper() - adjustment.lower() > adjustment.page_size()
+}
+
+// keeps the terminals of a page scrolled to the same distance from the bottom
+pub struct ScrollSync {
+    pub page: gtk::Widget,
+    terminals: Vec<vte::Terminal>,
+    handlers: Vec<(gtk::Adjustment, glib::SignalHandlerId)>,
+}
+
+impl ScrollSync {
+    // `on_lost` runs when a terminal drops its scrollback, which is what
+    // entering the alternate screen looks like from outside vte
+    pub fn new(page: &gtk::Widget, on_lost: impl Fn() + Clone + 'static) -> Option<Self> {
+        let terminals = terminals(page);
+        let adjustments: Vec<gtk::Adjustment> = terminals
+            .iter()
+            .filter_map(|terminal| terminal.vadjustment())
+            .collect();
+        if adjustments.len() < 2 {
+            return None;
+        }
+
+        let syncing = Rc::new(Cell::new(false));
+        let mut handlers = Vec::new();
+        for adjustment in &adjustments {
+            let others: Vec<gtk::Adjustment> = adjustments
+                .iter()
+                .filter(|&other| other != adjustment)
+                .cloned()
+                .collect();
+            let syncing = syncing.clone();
+            let id = adjustment.connect_value_changed(move |adjustment| {
+                if syncing.replace(true) {
+                    return;
+                }
+                let offset = from_bottom(adjustment);
+                for other in &others {
+                    other.set_value(other.upper() - other.page_size() - offset);
+                }
+                syncing.set(false);
+            });
+            handlers.push((adjustment.clone(), id));
+
+            let had_scrollback = Cell::new(has_scrollback(adjustment));
+            let on_lost = on_lost.clone();
+            let id = adjustment.connect_changed(move |adjustment| {
+                let now = has_scrollback(adjustment);
+                if had_scrollback.replace(now) && !now {
+                    on_lost();
+                }
+            });
+            handlers.push((adjustment.clone(), id));
+        }
+
+        Some(ScrollSync {
+            page: page.clone(),
+            terminals,
+            handlers,
+        })
+    }
+
+    pub fn contains(&self, terminal: &vte::Terminal) -> bool {
+        self.terminals.contains(terminal)
+    }
+}
+
+impl Drop for ScrollSync {
+    fn drop(&mut self) {
+        for (adjustment, id) in self.handlers.drain(..) {
+            adjustment.disconnect(id);
+        }
+    }
+}
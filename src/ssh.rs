@@ -0,0 +1,179 @@
+// Recognizing ssh sessions from the command line of the foreground process
+// or from the host part of the OSC 7 directory uri.
+
+use crate::tabs;
+
+// ssh options that take an argument, see ssh(1)
+const OPTIONS_WITH_ARG: &str = "BbcDEeFIiJLlmOoPpQRSWw";
+
+// colors for the tabs of hosts matching `pattern` while they're connected
+#[derive(Debug, Clone)]
+pub struct HostColors {
+    pub pattern: regex::Regex,
+    pub background: Option<gdk::RGBA>,
+    // blended into the background as much as its alpha says
+    pub tint: Option<gdk::RGBA>,
+}
+
+// a [[connections]] entry, opened from "Connect…" or `pterm ssh <name>`
+#[derive(Debug, Clone)]
+pub struct Connection {
+    pub name: String,
+    // [user@]host
+    pub destination: String,
+    pub port: Option<u16>,
+    pub mosh: bool,
+    pub title: Option<String>,
+    // run there instead of the login shell
+    pub command: Option<String>,
+    // instead of what [[hosts]] says
+    pub colors: tabs::Colors,
+}
+
+impl Connection {
+    // just the destination, for hosts without a [[connections]] entry
+    pub fn to(destination: &str, mosh: bool) -> Self {
+        Connection {
+            name: destination.to_string(),
+            destination: destination.to_string(),
+            port: None,
+            mosh,
+            title: None,
+            command: None,
+            colors: tabs::Colors::default(),
+        }
+    }
+
+    pub fn argv(&self) -> Vec<String> {
+        let mut argv = Vec::new();
+        if self.mosh {
+            argv.push("mosh".to_string());
+            if let Some(port) = self.port {
+                argv.push(format!("--ssh=ssh -p {}", port));
+            }
+            argv.push(self.destination.clone());
+            if let Some(command) = &self.command {
+                argv.extend(
+                    ["--", "sh", "-c", command]
+                        .iter()
+                        .map(|arg| arg.to_string()),
+                );
+            }
+        } else {
+            argv.push("ssh".to_string());
+            if let Some(port) = self.port {
+                argv.extend(["-p".to_string(), port.to_string()]);
+            }
+            if self.command.is_some() {
+                argv.push("-t".to_string());
+            }
+            argv.push(self.destination.clone());
+            argv.extend(self.command.clone());
+        }
+        argv
+    }
+}
+
+// shell style: * is anything, ? one character, case doesn't matter
+pub fn host_pattern(glob: &str) -> Result<regex::Regex, regex::Error> {
+    let pattern = regex::escape(glob).replace(r"\*", ".*").replace(r"\?", ".");
+    regex::Regex::new(&format!("(?i)^{}$", pattern))
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Remote {
+    pub user: Option<String>,
+    pub host: String,
+    // how to get there again, empty if we only know the host
+    pub argv: Vec<String>,
+}
+
+impl Remote {
+    pub fn title(&self, template: &str, local_user: &str) -> String {
+        template
+            .replace("{user}", self.user.as_deref().unwrap_or(local_user))
+            .replace("{remote}", &self.host)
+    }
+}
+
+pub fn is_ssh(argv: &[String]) -> bool {
+    argv.first()
+        .and_then(|arg0| std::path::Path::new(arg0).file_name())
+        .map(|name| name == "ssh")
+        .unwrap_or(false)
+}
+
+pub fn parse(argv: &[String]) -> Option<Remote> {
+    if !is_ssh(argv) {
+        return None;
+    }
+    let mut user = None;
+    let mut args = argv[1..].iter();
+    let destination = loop {
+        let arg = args.next()?;
+        if let Some(flags) = arg.strip_prefix('-') {
+            // flags can be bundled (-vp 22), the value may be attached (-p22)
+            for (i, flag) in flags.char_indices() {
+                if OPTIONS_WITH_ARG.contains(flag) {
+                    let attached = &flags[i + flag.len_utf8()..];
+                    let value = if attached.is_empty() {
+                        args.next().cloned()
+                    } else {
+                        Some(attached.to_string())
+                    };
+                    if flag == 'l' {
+                        user = value;
+                    }
+                    break;
+                }
+            }
+            continue;
+        }
+        break arg;
+    };
+
+    let destination = destination.strip_prefix("ssh://").unwrap_or(destination);
+    let (dest_user, host) = match destination.rsplit_once('@') {
+        Some((user, host)) => (Some(user.to_string()), host),
+        None => (None, destination),
+    };
+    // ssh://host:port
+    let host = host.split(':').next().unwrap_or(host);
+    Some(Remote {
+        user: dest_user.or(user),
+        host: host.to_string(),
+        argv: argv.to_vec(),
+    })
+}
+
+// OSC 7 reports file://host/path, a host other than ours means we're remote
+pub fn from_directory_uri(uri: &str, local_host: &str) -> Option<Remote> {
+    let rest = uri.strip_prefix("file://")?;
+    let host = &rest[..rest.find('/').unwrap_or(rest.len())];
+    if host.is_empty() || host == "localhost" || host == local_host {
+        return None;
+    }
+    Some(Remote {
+        user: None,
+        host: host.to_string(),
+        argv: Vec::new(),
+    })
+}
+
+// quoted for pasting into a posix shell
+pub fn shell_command(argv: &[String]) -> String {
+    argv.iter()
+        .map(|arg| {
+            let safe = !arg.is_empty()
+                && arg
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "@%+=:,./-_".contains(c));
+            if safe {
+                arg.clone()
+            } else {
+                format!("'{}'", arg.replace('\'', "'\\''"))
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
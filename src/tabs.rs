@@ -0,0 +1,294 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::{performance, platform, search, select, ssh, tmux, watch};
+
+const MAX_HIGHLIGHTS: usize = 10_000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TabId(u64);
+
+impl std::fmt::Display for TabId {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+// a background for a tab, and/or a tint blended into it as much as its alpha says
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Colors {
+    pub background: Option<gdk::RGBA>,
+    pub tint: Option<gdk::RGBA>,
+}
+
+impl Colors {
+    pub fn is_empty(&self) -> bool {
+        self.background.is_none() && self.tint.is_none()
+    }
+}
+
+// The child as returned by spawn. vte installs a child watch for it, so it's
+// reaped and we only get told through child-exited. The start time is there to
+// notice if the pid was reused by an unrelated process after that.
+pub struct Child {
+    pub pid: glib::Pid,
+    started: Option<u64>,
+}
+
+pub struct Meta {
+    pub child: Option<Child>,
+    pub font_scale: f64,
+    // what was spawned, kept for restoring and duplicating tabs
+    pub argv: Vec<String>,
+    pub cwd: Option<PathBuf>,
+    // KEY=VALUE pairs added to the inherited environment
+    pub env: Vec<String>,
+    // set by the user, takes precedence over the title set by the shell
+    pub title: Option<String>,
+    pub profile: Option<String>,
+    pub started: SystemTime,
+    // set while the spawn is in flight
+    pub cancellable: Option<gio::Cancellable>,
+    pub spawn_error: Option<String>,
+    // wait status of the child, once it's gone
+    pub exit_status: Option<i32>,
+    pub title_update_pending: bool,
+    // last ssh session seen in this tab, kept after it ends for reconnecting
+    pub remote: Option<ssh::Remote>,
+    pub remote_connected: bool,
+    pub pinned: bool,
+    pub focus_serial: u64,
+    // triggers added to this tab only
+    pub watches: Vec<watch::Trigger>,
+    // next row to check against the triggers
+    pub scanned_row: Option<i64>,
+    // by index into config triggers followed by watches
+    pub triggers_fired: HashMap<usize, Instant>,
+    pub highlights: VecDeque<(i64, gdk::RGBA)>,
+    // row and unix time output first reached it, rows ascending
+    pub timestamps: VecDeque<(i64, i64)>,
+    pub show_timestamps: bool,
+    // from shell integration: the running command and when it started
+    pub command: Option<(String, Instant)>,
+    // $PTERM_TOKEN of the tab's shell, and the seq of the last shell
+    // integration precmd that went into the history
+    pub shell_token: Option<String>,
+    pub recorded_seq: Option<u64>,
+    // the foreground process group leader and its command line, for
+    // title_rules, read again only when the leader changes
+    pub foreground: Option<(u32, Option<String>)>,
+    // how long the last long command ran, while it's shown in the title
+    pub took: Option<Duration>,
+    // cursor row when the tab was last left, output after it is new
+    pub seen_marker: Option<i64>,
+    // rows with an optional note, ascending
+    pub bookmarks: Vec<(i64, Option<String>)>,
+    // from the find bar, ascending
+    pub search_matches: Vec<search::Match>,
+    pub search_current: Option<usize>,
+    // made with the keyboard
+    pub selection: Option<select::Selection>,
+    // output stopped with Ctrl+S
+    pub paused: bool,
+    // locked at some point, lock_idle applies
+    pub sensitive: bool,
+    pub last_input: Instant,
+    // the child was killed by restart_shell, start it again when it exits
+    pub restarting: bool,
+    // total cpu time of the process tree when busy_cpu last looked
+    pub cpu_sample: Option<(Instant, f64)>,
+    // percent of a core since then, when above busy_cpu
+    pub busy: Option<u32>,
+    pub last_output: Instant,
+    // see hibernate.rs
+    pub hibernating: bool,
+    // vte match tags and the [[matches]] name they're for
+    pub match_tags: Vec<(i32, String)>,
+    // percent done, from a progress trigger
+    pub progress: Option<u8>,
+    // shows a pane of a tmux session, there's no child
+    pub tmux: Option<tmux::Pane>,
+    // instead of what [[hosts]] says, from [[connections]] or [kubernetes]
+    pub colors: Option<Colors>,
+    // name of the container the tab was opened in, for {container}
+    pub container: Option<String>,
+    // what the shell said at the last prompt: venv, branch, aws_profile
+    pub facts: BTreeMap<String, String>,
+    // "Show Frame Timing"
+    pub frames: Option<performance::Frames>,
+}
+
+impl Meta {
+    pub fn new(argv: Vec<String>, cwd: Option<PathBuf>) -> Self {
+        Meta {
+            child: None,
+            font_scale: 1.0,
+            argv,
+            cwd,
+            env: Vec::new(),
+            title: None,
+            profile: None,
+            started: SystemTime::now(),
+            cancellable: None,
+            spawn_error: None,
+            exit_status: None,
+            title_update_pending: false,
+            remote: None,
+            remote_connected: false,
+            pinned: false,
+            focus_serial: 0,
+            watches: Vec::new(),
+            scanned_row: None,
+            triggers_fired: HashMap::new(),
+            highlights: VecDeque::new(),
+            timestamps: VecDeque::new(),
+            show_timestamps: false,
+            command: None,
+            shell_token: None,
+            recorded_seq: None,
+            foreground: None,
+            took: None,
+            seen_marker: None,
+            bookmarks: Vec::new(),
+            search_matches: Vec::new(),
+            search_current: None,
+            selection: None,
+            paused: false,
+            sensitive: false,
+            last_input: Instant::now(),
+            restarting: false,
+            cpu_sample: None,
+            busy: None,
+            last_output: Instant::now(),
+            hibernating: false,
+            match_tags: Vec::new(),
+            progress: None,
+            tmux: None,
+            colors: None,
+            container: None,
+            facts: BTreeMap::new(),
+            frames: None,
+        }
+    }
+
+    // {placeholder} values for titles and badges
+    pub fn placeholders(&self) -> BTreeMap<String, String> {
+        let mut placeholders = self.facts.clone();
+        if let Some(container) = &self.container {
+            placeholders.insert("container".to_string(), container.clone());
+        }
+        placeholders
+    }
+
+    pub fn foreground_command(&mut self, pid: u32) -> Option<String> {
+        if !matches!(self.foreground, Some((cached, _)) if cached == pid) {
+            let command = platform::process_cmdline(pid).map(|argv| argv.join(" "));
+            self.foreground = Some((pid, command));
+        }
+        self.foreground
+            .as_ref()
+            .and_then(|(_, command)| command.clone())
+    }
+
+    // rows fall out of the scrollback eventually, so only the latest are kept
+    pub fn add_highlight(&mut self, row: i64, color: gdk::RGBA) {
+        if self.highlights.len() >= MAX_HIGHLIGHTS {
+            self.highlights.pop_front();
+        }
+        self.highlights.push_back((row, color));
+    }
+
+    pub fn set_child(&mut self, pid: glib::Pid) {
+        let started = platform::process_start_time(pid.0 as u32);
+        self.child = Some(Child { pid, started });
+    }
+
+    // pid of the child if it's still the process we spawned
+    pub fn live_pid(&self) -> Option<u32> {
+        let child = self.child.as_ref()?;
+        let pid = child.pid.0 as u32;
+        if child.started.is_some() && platform::process_start_time(pid) != child.started {
+            return None;
+        }
+        Some(pid)
+    }
+}
+
+// Tabs get an id as soon as they're created, the terminal widget is attached
+// later, so metadata can be filled in before the spawn completes.
+#[derive(Default)]
+pub struct TabStore {
+    next_id: u64,
+    meta: BTreeMap<TabId, Meta>,
+    terminals: BTreeMap<TabId, vte::Terminal>,
+    ids: HashMap<vte::Terminal, TabId>,
+}
+
+impl TabStore {
+    pub fn insert(&mut self, meta: Meta) -> TabId {
+        let id = TabId(self.next_id);
+        self.next_id += 1;
+        self.meta.insert(id, meta);
+        id
+    }
+
+    pub fn attach(&mut self, id: TabId, terminal: &vte::Terminal) {
+        if let Some(old) = self.terminals.insert(id, terminal.clone()) {
+            self.ids.remove(&old);
+        }
+        self.ids.insert(terminal.clone(), id);
+    }
+
+    // forgets the widget but keeps the metadata, e.g. while moving it elsewhere
+    pub fn detach(&mut self, terminal: &vte::Terminal) -> Option<TabId> {
+        let id = self.ids.remove(terminal)?;
+        self.terminals.remove(&id);
+        Some(id)
+    }
+
+    pub fn remove(&mut self, id: TabId) -> Option<Meta> {
+        if let Some(terminal) = self.terminals.remove(&id) {
+            self.ids.remove(&terminal);
+        }
+        self.meta.remove(&id)
+    }
+
+    pub fn remove_terminal(&mut self, terminal: &vte::Terminal) -> Option<Meta> {
+        let id = self.id(terminal)?;
+        self.remove(id)
+    }
+
+    pub fn id(&self, terminal: &vte::Terminal) -> Option<TabId> {
+        self.ids.get(terminal).copied()
+    }
+
+    pub fn terminal(&self, id: TabId) -> Option<&vte::Terminal> {
+        self.terminals.get(&id)
+    }
+
+    pub fn get(&self, id: TabId) -> Option<&Meta> {
+        self.meta.get(&id)
+    }
+
+    pub fn get_mut(&mut self, id: TabId) -> Option<&mut Meta> {
+        self.meta.get_mut(&id)
+    }
+
+    pub fn by_terminal(&self, terminal: &vte::Terminal) -> Option<&Meta> {
+        self.get(self.id(terminal)?)
+    }
+
+    pub fn by_terminal_mut(&mut self, terminal: &vte::Terminal) -> Option<&mut Meta> {
+        self.get_mut(self.id(terminal)?)
+    }
+
+    // attached terminals in creation order
+    pub fn terminals(&self) -> impl Iterator<Item = &vte::Terminal> {
+        self.terminals.values()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (TabId, &Meta)> {
+        self.meta.iter().map(|(id, meta)| (*id, meta))
+    }
+}
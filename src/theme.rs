@@ -0,0 +1,483 @@
+// `pterm import-theme`: color schemes of other terminals converted to our
+// [colors], optionally saved as ~/.config/pterm/themes/<name>.toml for the
+// `theme` option
+
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use anyhow::{bail, Error};
+use gtk::prelude::*;
+use gtk::subclass::prelude::*;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::config::ColorConfig;
+use crate::{hacks, Term, TermImpl};
+
+#[derive(Debug)]
+pub struct Import {
+    pub file: PathBuf,
+    pub install: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ThemeFile {
+    colors: ColorConfig,
+}
+
+// what the formats have in common: 0-15 for the palette
+#[derive(Debug, Default)]
+struct Colors {
+    foreground: Option<String>,
+    background: Option<String>,
+    palette: HashMap<usize, String>,
+    cursor: Option<String>,
+    cursor_foreground: Option<String>,
+}
+
+impl Colors {
+    fn into_config(self) -> Result<ColorConfig, Error> {
+        let mut missing: Vec<String> = Vec::new();
+        if self.foreground.is_none() {
+            missing.push("foreground".to_string());
+        }
+        if self.background.is_none() {
+            missing.push("background".to_string());
+        }
+        missing.extend(
+            (0..16)
+                .filter(|index| !self.palette.contains_key(index))
+                .map(|index| format!("color{}", index)),
+        );
+        if !missing.is_empty() {
+            bail!("no {} in the theme", missing.join(", "));
+        }
+        let mut palette = self.palette;
+        Ok(ColorConfig {
+            foreground: self.foreground.unwrap(),
+            background: self.background.unwrap(),
+            palette: (0..16)
+                .map(|index| palette.remove(&index).unwrap())
+                .collect(),
+            cursor: self.cursor,
+            cursor_foreground: self.cursor_foreground,
+        })
+    }
+}
+
+fn hex(red: f64, green: f64, blue: f64) -> String {
+    let byte = |c: f64| (c.clamp(0.0, 1.0) * 255.0).round() as u8;
+    format!("#{:02x}{:02x}{:02x}", byte(red), byte(green), byte(blue))
+}
+
+// "0x1d1f21", "1d1f21" and "#1d1f21" all mean the same
+fn normalize(value: &str) -> String {
+    let value = value.trim().trim_matches(|c| c == '"' || c == '\'');
+    let digits = value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix('#'))
+        .unwrap_or(value);
+    if digits.len() == 6 && digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        format!("#{}", digits.to_lowercase())
+    } else {
+        value.to_string()
+    }
+}
+
+// a plist of dicts with "Red Component" and friends, only the tags we need
+fn parse_iterm(text: &str) -> Colors {
+    let tokens = Regex::new(r"<(key|real|integer)>([^<]*)</\w+>|<(/?)dict>").unwrap();
+
+    let mut depth = 0;
+    let mut name = String::new();
+    let mut component = String::new();
+    let mut components: HashMap<String, [f64; 3]> = HashMap::new();
+    for token in tokens.captures_iter(text) {
+        if let Some(close) = token.get(3) {
+            depth += if close.as_str().is_empty() { 1 } else { -1 };
+            continue;
+        }
+        let value = token[2].trim();
+        match (depth, &token[1]) {
+            (1, "key") => name = value.to_string(),
+            (2, "key") => component = value.to_string(),
+            (2, _) => {
+                let channel = match component.as_str() {
+                    "Red Component" => 0,
+                    "Green Component" => 1,
+                    "Blue Component" => 2,
+                    _ => continue,
+                };
+                if let Ok(value) = value.parse::<f64>() {
+                    components.entry(name.clone()).or_default()[channel] = value;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut colors = Colors::default();
+    for (name, [red, green, blue]) in components {
+        let color = hex(red, green, blue);
+        match name.as_str() {
+            "Foreground Color" => colors.foreground = Some(color),
+            "Background Color" => colors.background = Some(color),
+            "Cursor Color" => colors.cursor = Some(color),
+            "Cursor Text Color" => colors.cursor_foreground = Some(color),
+            _ => {
+                let index = name
+                    .strip_prefix("Ansi ")
+                    .and_then(|name| name.strip_suffix(" Color"))
+                    .and_then(|index| index.parse::<usize>().ok());
+                if let Some(index) = index.filter(|&index| index < 16) {
+                    colors.palette.insert(index, color);
+                }
+            }
+        }
+    }
+    colors
+}
+
+// "*.color4: #6161afafefef", "URxvt.foreground: ...", comments start with !
+fn parse_xresources(text: &str) -> Colors {
+    let line =
+        Regex::new(r"^\s*[\w.*-]*?(foreground|background|cursorColor|color(\d+))\s*:\s*(\S+)")
+            .unwrap();
+    let mut colors = Colors::default();
+    for captures in text.lines().filter_map(|text| line.captures(text)) {
+        let value = normalize(&captures[3]);
+        match (&captures[1], captures.get(2)) {
+            ("foreground", _) => colors.foreground = Some(value),
+            ("background", _) => colors.background = Some(value),
+            ("cursorColor", _) => colors.cursor = Some(value),
+            (_, Some(index)) => {
+                if let Ok(index) = index.as_str().parse::<usize>() {
+                    if index < 16 {
+                        colors.palette.insert(index, value);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    colors
+}
+
+// "key: value" lines of a yaml file as (indentation, key, value), enough for
+// the flat shapes of base16 and alacritty schemes
+fn yaml_lines(text: &str) -> impl Iterator<Item = (usize, &str, &str)> {
+    text.lines().filter_map(|line| {
+        let line = line.split(" #").next().unwrap_or_default();
+        let indent = line.len() - line.trim_start().len();
+        let (key, value) = line.trim().split_once(':')?;
+        if key.starts_with('#') {
+            return None;
+        }
+        Some((indent, key.trim(), value.trim()))
+    })
+}
+
+// the mapping base16-shell uses
+const BASE16_PALETTE: [&str; 16] = [
+    "base00", "base08", "base0B", "base0A", "base0D", "base0E", "base0C", "base05", "base03",
+    "base08", "base0B", "base0A", "base0D", "base0E", "base0C", "base07",
+];
+
+fn parse_base16(text: &str) -> Colors {
+    let values: HashMap<String, String> = yaml_lines(text)
+        .map(|(_, key, value)| (key.to_string(), normalize(value)))
+        .collect();
+    let get = |key: &str| {
+        values
+            .get(key)
+            .or_else(|| values.get(&key.to_lowercase()))
+            .cloned()
+    };
+    let mut colors = Colors {
+        foreground: get("base05"),
+        background: get("base00"),
+        ..Colors::default()
+    };
+    for (index, key) in BASE16_PALETTE.iter().enumerate() {
+        if let Some(value) = get(key) {
+            colors.palette.insert(index, value);
+        }
+    }
+    colors
+}
+
+const ANSI_NAMES: [&str; 8] = [
+    "black", "red", "green", "yellow", "blue", "magenta", "cyan", "white",
+];
+
+// colors: {primary: {foreground, background}, cursor: {text, cursor}, normal:
+// {black, ...}, bright: {...}}
+fn parse_alacritty(text: &str) -> Colors {
+    let mut colors = Colors::default();
+    let mut section: Option<(usize, String)> = None;
+    for (indent, key, value) in yaml_lines(text) {
+        if value.is_empty() {
+            section = Some((indent, key.to_string()));
+            continue;
+        }
+        let section = match &section {
+            Some((section_indent, section)) if indent > *section_indent => section.as_str(),
+            _ => continue,
+        };
+        let value = normalize(value);
+        match (section, key) {
+            ("primary", "foreground") => colors.foreground = Some(value),
+            ("primary", "background") => colors.background = Some(value),
+            ("cursor", "cursor") => colors.cursor = Some(value),
+            ("cursor", "text") => colors.cursor_foreground = Some(value),
+            ("normal" | "bright", _) => {
+                if let Some(index) = ANSI_NAMES.iter().position(|&name| name == key) {
+                    let offset = if section == "bright" { 8 } else { 0 };
+                    colors.palette.insert(index + offset, value);
+                }
+            }
+            _ => {}
+        }
+    }
+    colors
+}
+
+fn convert(path: &Path, text: &str) -> Colors {
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or_default();
+    match extension {
+        "itermcolors" => parse_iterm(text),
+        "yaml" | "yml" if text.contains("base00") => parse_base16(text),
+        "yaml" | "yml" => parse_alacritty(text),
+        _ => parse_xresources(text),
+    }
+}
+
+fn check_name(name: &str) -> Result<(), Error> {
+    if name.is_empty() || name.starts_with('.') || name.contains(std::path::is_separator) {
+        bail!("theme names can't be empty, start with a dot or contain slashes");
+    }
+    Ok(())
+}
+
+fn file_name(name: &str) -> String {
+    format!("themes/{}.toml", name)
+}
+
+// prints the [colors] block, or saves it and says where
+pub fn import(import: &Import) -> Result<(), Error> {
+    let text = std::fs::read_to_string(&import.file)?;
+    let colors = convert(&import.file, &text).into_config()?;
+    let toml = toml::to_string(&ThemeFile { colors })?;
+    match &import.install {
+        None => print!("{}", toml),
+        Some(name) => {
+            check_name(name)?;
+            let dirs = xdg::BaseDirectories::with_prefix("pterm")?;
+            let path = dirs.place_config_file(file_name(name))?;
+            std::fs::write(&path, toml)?;
+            println!(
+                "saved to {}, use it with theme = {:?} in the config",
+                path.display(),
+                name
+            );
+        }
+    }
+    Ok(())
+}
+
+// colors of an installed theme
+pub fn load(name: &str) -> Result<ColorConfig, Error> {
+    check_name(name)?;
+    let dirs = xdg::BaseDirectories::with_prefix("pterm")?;
+    let path = match dirs.find_config_file(file_name(name)) {
+        Some(path) => path,
+        None => bail!("no theme {:?}", name),
+    };
+    let file: ThemeFile = toml::from_slice(&std::fs::read(path)?)?;
+    Ok(file.colors)
+}
+
+pub fn saved_names() -> Vec<String> {
+    let dirs = match xdg::BaseDirectories::with_prefix("pterm") {
+        Ok(dirs) => dirs,
+        Err(_) => return Vec::new(),
+    };
+    let mut names: Vec<String> = dirs
+        .list_config_files("themes")
+        .into_iter()
+        .filter(|path| path.extension().map(|ext| ext == "toml").unwrap_or(false))
+        .filter_map(|path| Some(path.file_stem()?.to_str()?.to_string()))
+        .collect();
+    names.sort();
+    names.dedup();
+    names
+}
+
+// the line the theme goes on, everything else in the file is kept as it is
+fn set_theme(config: &str, name: &str) -> String {
+    let line = format!("theme = {:?}", name);
+    let mut lines: Vec<String> = config.lines().map(String::from).collect();
+    // only top level keys, before the first table
+    let top = lines
+        .iter()
+        .position(|line| line.trim_start().starts_with('['))
+        .unwrap_or(lines.len());
+    let existing = lines[..top].iter().position(|line| {
+        let line = line.trim_start().trim_start_matches('#').trim_start();
+        line.strip_prefix("theme")
+            .map(|rest| rest.trim_start().starts_with('='))
+            .unwrap_or(false)
+    });
+    match existing {
+        Some(index) => lines[index] = line,
+        None => {
+            lines.insert(top, String::new());
+            lines.insert(top, line);
+        }
+    }
+    let mut config = lines.join("\n");
+    config.push('\n');
+    config
+}
+
+// foreground, background and palette
+type Palette = (gdk::RGBA, gdk::RGBA, Vec<gdk::RGBA>);
+
+fn parse(colors: &ColorConfig) -> Option<Palette> {
+    Some((
+        hacks::parse_color(&colors.foreground).ok()?,
+        hacks::parse_color(&colors.background).ok()?,
+        colors
+            .palette
+            .iter()
+            .map(|color| hacks::parse_color(color).ok())
+            .collect::<Option<Vec<_>>>()?,
+    ))
+}
+
+fn show_colors(term: &Term, (foreground, background, palette): &Palette) {
+    let imp = TermImpl::from_instance(term);
+    {
+        let mut config = imp.config.borrow_mut();
+        config.foreground = *foreground;
+        config.background = *background;
+        config.palette = palette.clone();
+    }
+    let terminals: Vec<vte::Terminal> = imp.tabs.borrow().terminals().cloned().collect();
+    for terminal in &terminals {
+        term.apply_colors(terminal);
+    }
+    term.apply_window_colors();
+}
+
+struct Preview {
+    // None is what's in use now
+    themes: Vec<(Option<String>, Palette)>,
+    index: Cell<usize>,
+    label: gtk::Label,
+    banner: gtk::InfoBar,
+    handler: RefCell<Option<glib::SignalHandlerId>>,
+}
+
+impl Preview {
+    fn show(&self, term: &Term) {
+        let (name, colors) = &self.themes[self.index.get()];
+        show_colors(term, colors);
+        self.label.set_text(&format!(
+            "{} ({}/{}) \u{2190}/\u{2192} to switch, Enter to keep, Esc to cancel",
+            name.as_deref().unwrap_or("current colors"),
+            self.index.get() + 1,
+            self.themes.len()
+        ));
+    }
+
+    fn finish(&self, term: &Term, accept: bool) {
+        if let Some(handler) = self.handler.borrow_mut().take() {
+            term.disconnect(handler);
+        }
+        self.banner.hide();
+        self.banner.destroy();
+
+        let name = match (accept, &self.themes[self.index.get()].0) {
+            (true, Some(name)) => name.clone(),
+            _ => return show_colors(term, &self.themes[0].1),
+        };
+        let path = TermImpl::from_instance(term).config_path.borrow().clone();
+        let written = std::fs::read_to_string(&path)
+            .and_then(|config| std::fs::write(&path, set_theme(&config, &name)));
+        match written {
+            Ok(()) => term.reload_config(),
+            Err(err) => {
+                show_colors(term, &self.themes[0].1);
+                term.show_warning(&format!("failed to save theme {:?}: {}", name, err));
+            }
+        }
+    }
+}
+
+// tries the installed themes on the open terminals, Left/Right switch,
+// Enter writes the one shown to the config and Esc goes back
+pub fn preview(term: &Term) {
+    let imp = TermImpl::from_instance(term);
+    let current = {
+        let config = imp.config.borrow();
+        (config.foreground, config.background, config.palette.clone())
+    };
+    let mut themes = vec![(None, current)];
+    for name in saved_names() {
+        match load(&name).ok().as_ref().and_then(parse) {
+            Some(colors) => themes.push((Some(name), colors)),
+            None => log::error!("theme {:?} can't be loaded", name),
+        }
+    }
+    if themes.len() == 1 {
+        term.show_warning("no themes installed, see pterm import-theme");
+        return;
+    }
+
+    let banner = term.banner(gtk::MessageType::Info, "");
+    let label = banner
+        .content_area()
+        .children()
+        .into_iter()
+        .find_map(|child| child.downcast::<gtk::Label>().ok())
+        .unwrap();
+    let preview = Rc::new(Preview {
+        themes,
+        index: Cell::new(0),
+        label,
+        banner: banner.clone(),
+        handler: RefCell::new(None),
+    });
+    banner.connect_response(glib::clone!(@weak term, @strong preview => move |_, _| {
+        preview.finish(&term, false);
+    }));
+
+    let handler =
+        term.connect_key_press_event(glib::clone!(@strong preview => move |term, event| {
+            let count = preview.themes.len();
+            let index = preview.index.get();
+            match event.keyval() {
+                gdk::keys::constants::Left => preview.index.set((index + count - 1) % count),
+                gdk::keys::constants::Right => preview.index.set((index + 1) % count),
+                gdk::keys::constants::Return => {
+                    preview.finish(term, true);
+                    return Inhibit(true);
+                }
+                gdk::keys::constants::Escape => {
+                    preview.finish(term, false);
+                    return Inhibit(true);
+                }
+                _ => return Inhibit(false),
+            }
+            preview.show(term);
+            Inhibit(true)
+        }));
+    *preview.handler.borrow_mut() = Some(handler);
+    preview.show(term);
+}
@@ -0,0 +1,56 @@
+// [[title_rules]]: a tab whose command, cwd and host match a rule is titled
+// as the rule says, the first rule that matches wins.
+
+use std::path::Path;
+
+use regex::Regex;
+
+#[derive(Debug, Clone)]
+pub struct TitleRule {
+    pub command: Option<Regex>,
+    pub cwd: Option<Regex>,
+    pub host: Option<Regex>,
+    // {command}, {cwd} and {host}, and what shell integration reports
+    pub title: String,
+}
+
+// what is matched against
+pub struct Tab<'a> {
+    // the foreground process, or the command the tab was opened with
+    pub command: &'a str,
+    pub cwd: Option<&'a Path>,
+    // while ssh is connected somewhere
+    pub host: Option<&'a str>,
+}
+
+impl TitleRule {
+    fn matches(&self, tab: &Tab) -> bool {
+        let cwd = tab.cwd.map(|cwd| cwd.to_string_lossy());
+        let matches = |regex: &Option<Regex>, text: Option<&str>| match (regex, text) {
+            (None, _) => true,
+            (Some(regex), Some(text)) => regex.is_match(text),
+            (Some(_), None) => false,
+        };
+        matches(&self.command, Some(tab.command))
+            && matches(&self.cwd, cwd.as_deref())
+            && matches(&self.host, tab.host)
+    }
+
+    fn title(&self, tab: &Tab) -> String {
+        let cwd = tab
+            .cwd
+            .map(|cwd| cwd.display().to_string())
+            .unwrap_or_default();
+        self.title
+            .replace("{command}", tab.command)
+            .replace("{cwd}", &cwd)
+            .replace("{host}", tab.host.unwrap_or_default())
+    }
+}
+
+pub fn title(rules: &[TitleRule], tab: &Tab) -> Option<String> {
+    rules
+        .iter()
+        .find(|rule| rule.matches(tab))
+        .map(|rule| rule.title(tab))
+}
@@ -0,0 +1,880 @@
+// tmux control mode: pterm runs `tmux -C …` itself and shows the session's
+// windows as tabs and their panes as splits, so a session on a remote host
+// (`ssh host tmux -C new -A`) outlives pterm and is picked up again by
+// attaching. vte keeps the DCS sequence `tmux -CC` starts with to itself, so
+// control mode can't be noticed in a running tab the way iTerm2 does.
+//
+// tmux sends one line per notification: `%output %<pane> <octal escaped>` is
+// fed to the pane, the rest say which windows and panes came and went. Every
+// command sent gets a `%begin` … `%end` (or `%error`) block back, in order.
+// Panes are laid out like tmux lays them out when they appear, their sizes are
+// whatever the splits give them and tmux is told about them.
+//
+// With detachable on every window is a session of pterm's own tmux server:
+// new tabs and splits are made by tmux, closing the window detaches and
+// `pterm attach` opens a window for each session nothing is attached to.
+
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::ffi::{OsStr, OsString};
+use std::path::Path;
+use std::rc::Rc;
+
+use gtk::prelude::*;
+use vte::TerminalExt;
+
+use crate::config::TerminalConfig;
+use crate::tabs::Meta;
+use crate::{split, Env, Term, TermImpl};
+
+pub const DEFAULT_COMMAND: &str = "tmux -C new-session -A -s pterm";
+// tmux -L, the server detachable windows use
+const SERVER: &str = "pterm";
+
+// what window, pane and layout lines are printed as
+const WINDOW_FORMAT: &str = "\"#{window_id} #{window_layout} #{window_name}\"";
+// longest key run in one send-keys
+const KEYS_CHUNK: usize = 256;
+const READ_SIZE: usize = 64 * 1024;
+
+type Reply = Box<dyn FnOnce(&Rc<Session>, Vec<String>)>;
+
+// set on the Meta of every pane that's shown for a tmux pane
+#[derive(Clone)]
+pub struct Pane {
+    pub session: Rc<Session>,
+    // "%<n>"
+    pub id: String,
+}
+
+pub struct Session {
+    term: glib::WeakRef<Term>,
+    stdin: gio::OutputStream,
+    state: RefCell<State>,
+}
+
+#[derive(Default)]
+struct State {
+    replies: VecDeque<Reply>,
+    // the time and number of the %begin block being read, and its lines
+    block: Option<(String, Vec<String>)>,
+    // the first block answers the command tmux was started with
+    started: bool,
+    // by tmux id, "@<n>" and "%<n>"
+    windows: HashMap<String, Vec<String>>,
+    panes: HashMap<String, vte::Terminal>,
+    // the size tmux was last told about, per pane and for the client
+    sizes: HashMap<String, (i64, i64)>,
+    ended: bool,
+}
+
+#[derive(Debug)]
+enum Layout {
+    Pane(String),
+    Split(gtk::Orientation, Vec<Layout>),
+}
+
+impl Layout {
+    // "b25f,80x24,0,0{40x24,0,0,1,39x24,41,0,2}", each cell is
+    // WxH,X,Y followed by its pane number or by its cells in {} side by side
+    // or in [] top to bottom
+    fn parse(text: &str) -> Option<Layout> {
+        let (_checksum, cells) = text.split_once(',')?;
+        let mut parser = Parser {
+            text: cells.as_bytes(),
+            at: 0,
+        };
+        let layout = parser.cell()?;
+        Some(layout).filter(|_| parser.at == parser.text.len())
+    }
+
+    fn first(&self) -> &str {
+        match self {
+            Layout::Pane(id) => id,
+            Layout::Split(_, cells) => cells[0].first(),
+        }
+    }
+
+    // with the direction of the split each is in
+    fn panes(&self, orientation: gtk::Orientation, panes: &mut Vec<(String, gtk::Orientation)>) {
+        match self {
+            Layout::Pane(id) => panes.push((id.clone(), orientation)),
+            Layout::Split(orientation, cells) => {
+                for cell in cells {
+                    cell.panes(*orientation, panes);
+                }
+            }
+        }
+    }
+}
+
+struct Parser<'a> {
+    text: &'a [u8],
+    at: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<u8> {
+        self.text.get(self.at).copied()
+    }
+
+    fn eat(&mut self, byte: u8) -> Option<()> {
+        if self.peek()? != byte {
+            return None;
+        }
+        self.at += 1;
+        Some(())
+    }
+
+    fn number(&mut self) -> Option<&'a str> {
+        let start = self.at;
+        while self.peek().map(|c| c.is_ascii_digit()).unwrap_or(false) {
+            self.at += 1;
+        }
+        Some(std::str::from_utf8(&self.text[start..self.at]).ok()?).filter(|n| !n.is_empty())
+    }
+
+    fn cell(&mut self) -> Option<Layout> {
+        self.number()?;
+        self.eat(b'x')?;
+        self.number()?;
+        self.eat(b',')?;
+        self.number()?;
+        self.eat(b',')?;
+        self.number()?;
+        let (close, orientation) = match self.peek()? {
+            b',' => {
+                self.at += 1;
+                return Some(Layout::Pane(format!("%{}", self.number()?)));
+            }
+            b'{' => (b'}', gtk::Orientation::Horizontal),
+            b'[' => (b']', gtk::Orientation::Vertical),
+            _ => return None,
+        };
+        self.at += 1;
+        let mut cells = vec![self.cell()?];
+        while self.peek() == Some(b',') {
+            self.at += 1;
+            cells.push(self.cell()?);
+        }
+        self.eat(close)?;
+        Some(Layout::Split(orientation, cells))
+    }
+}
+
+// %begin, %end and %error go on with "<time> <number> <flags>", the last two
+// are only the block's end with the time and number it began with
+fn guard(rest: &str) -> String {
+    rest.splitn(3, ' ').take(2).collect::<Vec<_>>().join(" ")
+}
+
+fn closes(guard: &str, rest: &str) -> bool {
+    self::guard(rest) == guard
+}
+
+// `\ooo` for control characters and the backslash, everything else as is
+fn unescape(text: &[u8]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(text.len());
+    let mut at = 0;
+    while at < text.len() {
+        let octal = Some(text[at])
+            .filter(|&c| c == b'\\')
+            .and_then(|_| text.get(at + 1..at + 4))
+            .and_then(|digits| std::str::from_utf8(digits).ok())
+            .and_then(|digits| u8::from_str_radix(digits, 8).ok());
+        match octal {
+            Some(byte) => {
+                bytes.push(byte);
+                at += 4;
+            }
+            None => {
+                bytes.push(text[at]);
+                at += 1;
+            }
+        }
+    }
+    bytes
+}
+
+// "Attach tmux Session…": the command can be anything that ends up running
+// tmux -C, e.g. over ssh
+pub fn attach(term: &Term) {
+    let dialog = gtk::Dialog::with_buttons(
+        Some("Attach tmux Session"),
+        Some(term),
+        gtk::DialogFlags::MODAL | gtk::DialogFlags::DESTROY_WITH_PARENT,
+        &[
+            ("Cancel", gtk::ResponseType::Cancel),
+            ("Attach", gtk::ResponseType::Ok),
+        ],
+    );
+    dialog.set_default_response(gtk::ResponseType::Ok);
+    let entry = gtk::Entry::builder()
+        .text(DEFAULT_COMMAND)
+        .activates_default(true)
+        .width_chars(40)
+        .build();
+    dialog.content_area().pack_start(&entry, false, false, 6);
+
+    dialog.connect_response(
+        glib::clone!(@weak term, @weak entry => move |dialog, response| {
+            let command = entry.text().trim().to_string();
+            dialog.close();
+            if response != gtk::ResponseType::Ok || command.is_empty() {
+                return;
+            }
+            match glib::shell_parse_argv(&command) {
+                Ok(argv) => start(&term, &argv),
+                Err(err) => term.show_warning(&format!("can't parse {}: {}", command, err)),
+            }
+        }),
+    );
+    dialog.show_all();
+}
+
+// false when tmux couldn't be run
+pub fn start(term: &Term, argv: &[OsString]) -> bool {
+    let args: Vec<&OsStr> = argv.iter().map(|arg| arg.as_os_str()).collect();
+    let flags = gio::SubprocessFlags::STDIN_PIPE | gio::SubprocessFlags::STDOUT_PIPE;
+    let process = match gio::Subprocess::newv(&args, flags) {
+        Ok(process) => process,
+        Err(err) => {
+            term.show_warning(&format!("can't run {}: {}", argv[0].to_string_lossy(), err));
+            return false;
+        }
+    };
+    let (stdin, stdout) = match (process.stdin_pipe(), process.stdout_pipe()) {
+        (Some(stdin), Some(stdout)) => (stdin, stdout),
+        _ => return false,
+    };
+    let session = Rc::new(Session {
+        term: term.downgrade(),
+        stdin,
+        state: RefCell::new(State::default()),
+    });
+    read(&session, &stdout, Vec::new());
+    true
+}
+
+fn server_command(args: &[&str]) -> Vec<OsString> {
+    ["tmux", "-L", SERVER, "-C"]
+        .iter()
+        .chain(args)
+        .map(OsString::from)
+        .collect()
+}
+
+// the first tab of a detachable window
+pub fn new_session(term: &Term, command: Option<&[String]>) -> bool {
+    let mut argv = server_command(&["new-session"]);
+    argv.extend(command.into_iter().flatten().map(OsString::from));
+    start(term, &argv)
+}
+
+// sessions of pterm's server with no client
+fn detached() -> Vec<String> {
+    let output = std::process::Command::new("tmux")
+        .args([
+            "-L",
+            SERVER,
+            "list-sessions",
+            "-F",
+            "#{session_attached} #{session_name}",
+        ])
+        .output();
+    match output {
+        Ok(output) if output.status.success() => String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter_map(|line| line.strip_prefix("0 "))
+            .map(String::from)
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+// the first window gets `loaded`, see Term::new
+pub fn attach_detached(
+    app: &gtk::Application,
+    config_path: &Path,
+    mut loaded: Option<(TerminalConfig, Vec<String>)>,
+) {
+    let sessions = detached();
+    if sessions.is_empty() {
+        match app
+            .active_window()
+            .and_then(|window| window.downcast::<Term>().ok())
+        {
+            Some(term) => term.show_warning("no detached sessions to attach"),
+            None => log::warn!("no detached sessions to attach"),
+        }
+        return;
+    }
+    for name in sessions {
+        let term = Term::without_tabs(
+            app,
+            Env::default(),
+            config_path.to_path_buf(),
+            loaded.take(),
+        );
+        start(
+            &term,
+            &server_command(&["attach-session", "-t", &format!("={}", name)]),
+        );
+        term.show_all();
+    }
+}
+
+fn active_pane(term: &Term) -> Option<Pane> {
+    let terminal = term.active_terminal()?;
+    let imp = TermImpl::from_instance(term);
+    let tabs = imp.tabs.borrow();
+    tabs.by_terminal(&terminal)?.tmux.clone()
+}
+
+// false when the active tab isn't tmux's, so it's an ordinary new tab
+pub fn new_window(term: &Term) -> bool {
+    match active_pane(term) {
+        Some(pane) => {
+            pane.session.send(&format!(
+                "new-window -a -t {} -c \"#{{pane_current_path}}\"",
+                pane.id
+            ));
+            true
+        }
+        None => false,
+    }
+}
+
+pub fn split(term: &Term, terminal: &vte::Terminal, orientation: gtk::Orientation) -> bool {
+    let pane = TermImpl::from_instance(term)
+        .tabs
+        .borrow()
+        .by_terminal(terminal)
+        .and_then(|meta| meta.tmux.clone());
+    let pane = match pane {
+        Some(pane) => pane,
+        None => return false,
+    };
+    let direction = match orientation {
+        gtk::Orientation::Vertical => "-v",
+        _ => "-h",
+    };
+    pane.session.send(&format!(
+        "split-window {} -t {} -c \"#{{pane_current_path}}\"",
+        direction, pane.id
+    ));
+    true
+}
+
+// the window is going away, its sessions stay
+pub fn detach_all(term: &Term) {
+    let mut sessions: Vec<Rc<Session>> = Vec::new();
+    {
+        let imp = TermImpl::from_instance(term);
+        let tabs = imp.tabs.borrow();
+        let panes = tabs
+            .terminals()
+            .filter_map(|terminal| tabs.by_terminal(terminal))
+            .filter_map(|meta| meta.tmux.as_ref());
+        for pane in panes {
+            if !sessions
+                .iter()
+                .any(|session| Rc::ptr_eq(session, &pane.session))
+            {
+                sessions.push(pane.session.clone());
+            }
+        }
+    }
+    for session in sessions {
+        let mut state = session.state.borrow_mut();
+        if std::mem::replace(&mut state.ended, true) {
+            continue;
+        }
+        state.windows.clear();
+        state.panes.clear();
+        drop(state);
+        session.send("detach-client");
+    }
+}
+
+// split into lines here: read_line_async can't tell an empty line, which
+// capture-pane output is full of, from the end of the stream
+fn read(session: &Rc<Session>, input: &gio::InputStream, pending: Vec<u8>) {
+    input.read_bytes_async(
+        READ_SIZE,
+        glib::PRIORITY_DEFAULT,
+        None::<&gio::Cancellable>,
+        glib::clone!(@strong session, @strong input => move |result| {
+            let mut pending = pending;
+            match result {
+                Ok(bytes) if bytes.is_empty() => session.ended("tmux exited"),
+                Ok(bytes) => {
+                    pending.extend_from_slice(&bytes);
+                    while let Some(end) = pending.iter().position(|&c| c == b'\n') {
+                        let line: Vec<u8> = pending.drain(..=end).collect();
+                        session.line(&line[..end]);
+                    }
+                    read(&session, &input, pending);
+                }
+                Err(err) => session.ended(&format!("tmux: {}", err)),
+            }
+        }),
+    );
+}
+
+// sends what's typed into a tmux pane to tmux, as hex so nothing needs quoting
+pub fn commit(term: &Term, terminal: &vte::Terminal, text: &str) {
+    let pane = match TermImpl::from_instance(term)
+        .tabs
+        .borrow()
+        .by_terminal(terminal)
+        .and_then(|meta| meta.tmux.clone())
+    {
+        Some(pane) => pane,
+        None => return,
+    };
+    for chunk in text.as_bytes().chunks(KEYS_CHUNK) {
+        let keys: Vec<String> = chunk.iter().map(|byte| format!("{:02x}", byte)).collect();
+        pane.session
+            .send(&format!("send-keys -t {} -H {}", pane.id, keys.join(" ")));
+    }
+}
+
+// a tmux pane's terminal went away here, so it goes in tmux too
+pub fn forget(pane: &Pane) {
+    let known = {
+        let mut state = pane.session.state.borrow_mut();
+        for panes in state.windows.values_mut() {
+            panes.retain(|id| id != &pane.id);
+        }
+        state.panes.remove(&pane.id).is_some() && !state.ended
+    };
+    if known {
+        pane.session.send(&format!("kill-pane -t {}", pane.id));
+    }
+}
+
+// "Detach tmux": the session keeps running and its tabs close
+pub fn detach(term: &Term) {
+    match active_pane(term) {
+        Some(pane) => pane.session.send("detach-client"),
+        None => term.show_warning("this tab isn't showing a tmux session"),
+    }
+}
+
+impl Session {
+    fn send(self: &Rc<Self>, command: &str) {
+        self.command(command, |_, _| {});
+    }
+
+    fn command(
+        self: &Rc<Self>,
+        command: &str,
+        reply: impl FnOnce(&Rc<Session>, Vec<String>) + 'static,
+    ) {
+        log::debug!("tmux < {}", command);
+        let line = format!("{}\n", command);
+        if let Err(err) = self
+            .stdin
+            .write_all(line.as_bytes(), None::<&gio::Cancellable>)
+        {
+            log::warn!("can't write to tmux: {}", err);
+            return;
+        }
+        self.state.borrow_mut().replies.push_back(Box::new(reply));
+    }
+
+    fn line(self: &Rc<Self>, line: &[u8]) {
+        if let Some(output) = line.strip_prefix(b"%output ") {
+            let (pane, data) = match output.iter().position(|&c| c == b' ') {
+                Some(at) => (&output[..at], &output[at + 1..]),
+                None => return,
+            };
+            let terminal = self
+                .state
+                .borrow()
+                .panes
+                .get(&*String::from_utf8_lossy(pane))
+                .cloned();
+            if let Some(terminal) = terminal {
+                terminal.feed(&unescape(data));
+            }
+            return;
+        }
+        let line = String::from_utf8_lossy(line).into_owned();
+        let mut words = line.splitn(2, ' ');
+        let (keyword, rest) = (
+            words.next().unwrap_or_default(),
+            words.next().unwrap_or_default(),
+        );
+        let block = self.state.borrow_mut().block.take();
+        if let Some((guard, mut block)) = block {
+            // pane contents in a reply can have lines like that too
+            let ends = matches!(keyword, "%end" | "%error") && closes(&guard, rest);
+            match keyword {
+                "%end" if ends => self.answered(block),
+                "%error" if ends => {
+                    log::warn!("tmux: {}", block.join("\n"));
+                    let mut state = self.state.borrow_mut();
+                    if std::mem::replace(&mut state.started, true) {
+                        state.replies.pop_front();
+                    }
+                }
+                _ => {
+                    block.push(line);
+                    self.state.borrow_mut().block = Some((guard, block));
+                }
+            }
+            return;
+        }
+        log::debug!("tmux > {}", line);
+        match keyword {
+            "%begin" => self.state.borrow_mut().block = Some((guard(rest), Vec::new())),
+            "%window-add" => self.command(
+                &format!("display-message -p -t {} {}", rest, WINDOW_FORMAT),
+                |session, lines| lines.iter().for_each(|line| session.window(line)),
+            ),
+            "%layout-change" => {
+                let window = rest.split(' ').next().unwrap_or_default();
+                let known = self.state.borrow().windows.contains_key(window);
+                if known {
+                    self.window(rest);
+                }
+            }
+            "%window-close" | "%unlinked-window-close" => self.window_closed(rest),
+            "%window-renamed" => {
+                if let Some((window, name)) = rest.split_once(' ') {
+                    self.renamed(window, name);
+                }
+            }
+            "%exit" => self.ended(&match rest {
+                "" => "tmux detached".to_string(),
+                reason => format!("tmux detached: {}", reason),
+            }),
+            _ => {}
+        }
+    }
+
+    fn answered(self: &Rc<Self>, lines: Vec<String>) {
+        let started = std::mem::replace(&mut self.state.borrow_mut().started, true);
+        if !started {
+            self.command(
+                &format!("list-windows -F {}", WINDOW_FORMAT),
+                |session, lines| lines.iter().for_each(|line| session.window(line)),
+            );
+            return;
+        }
+        let reply = self.state.borrow_mut().replies.pop_front();
+        if let Some(reply) = reply {
+            reply(self, lines);
+        }
+    }
+
+    // "@<n> <layout> [name]": opens the window as a tab, or brings the splits
+    // of one that's open in line with the layout
+    fn window(self: &Rc<Self>, line: &str) {
+        let mut fields = line.splitn(3, ' ');
+        let (window, layout) = match (fields.next(), fields.next().and_then(Layout::parse)) {
+            (Some(window), Some(layout)) => (window.to_string(), layout),
+            _ => {
+                log::warn!("unexpected tmux window: {}", line);
+                return;
+            }
+        };
+        let name = fields.next().map(String::from);
+        let term = match self.term.upgrade() {
+            Some(term) => term,
+            None => return,
+        };
+        let mut panes = Vec::new();
+        layout.panes(gtk::Orientation::Horizontal, &mut panes);
+        let open = self.state.borrow().windows.get(&window).cloned();
+        let open = match open {
+            Some(open) => open,
+            None => {
+                let first = self.pane(&term, &window, layout.first(), name.as_deref());
+                let page = split::new_page(&first);
+                term.insert_tab(&page, &first);
+                self.place(&term, &window, &layout, &first, name.as_deref());
+                first.grab_focus();
+                return;
+            }
+        };
+
+        let terminal = |id: &str| self.state.borrow().panes.get(id).cloned();
+        for (index, (id, orientation)) in panes.iter().enumerate() {
+            if open.contains(id) {
+                continue;
+            }
+            // next to the pane before it, or after it when it's first
+            let neighbour = panes[..index]
+                .iter()
+                .rev()
+                .chain(panes[index + 1..].iter())
+                .find_map(|(id, _)| terminal(id));
+            if let Some(neighbour) = neighbour {
+                let new = self.pane(&term, &window, id, name.as_deref());
+                split::split(&neighbour, &new, *orientation);
+            }
+        }
+        for id in open {
+            if panes.iter().any(|(pane, _)| *pane == id) {
+                continue;
+            }
+            let removed = {
+                let mut state = self.state.borrow_mut();
+                if let Some(panes) = state.windows.get_mut(&window) {
+                    panes.retain(|pane| *pane != id);
+                }
+                state.panes.remove(&id)
+            };
+            if let Some(terminal) = removed {
+                term.close_pane(&terminal);
+            }
+        }
+    }
+
+    // the cells of `layout` around `first`, already in place for its first pane
+    fn place(
+        self: &Rc<Self>,
+        term: &Term,
+        window: &str,
+        layout: &Layout,
+        first: &vte::Terminal,
+        name: Option<&str>,
+    ) {
+        let (orientation, cells) = match layout {
+            Layout::Pane(_) => return,
+            Layout::Split(orientation, cells) => (*orientation, cells),
+        };
+        let mut firsts = vec![first.clone()];
+        for cell in &cells[1..] {
+            let new = self.pane(term, window, cell.first(), name);
+            split::split(firsts.last().unwrap(), &new, orientation);
+            firsts.push(new);
+        }
+        for (cell, first) in cells.iter().zip(&firsts) {
+            self.place(term, window, cell, first, name);
+        }
+    }
+
+    fn pane(
+        self: &Rc<Self>,
+        term: &Term,
+        window: &str,
+        id: &str,
+        name: Option<&str>,
+    ) -> vte::Terminal {
+        let mut meta = Meta::new(vec!["tmux".to_string()], None);
+        meta.title = name.map(String::from);
+        meta.tmux = Some(Pane {
+            session: self.clone(),
+            id: id.to_string(),
+        });
+        let terminal = term.new_pane_for(meta);
+        {
+            let mut state = self.state.borrow_mut();
+            state
+                .windows
+                .entry(window.to_string())
+                .or_default()
+                .push(id.to_string());
+            state.panes.insert(id.to_string(), terminal.clone());
+        }
+        let id = id.to_string();
+        let session = self.clone();
+        terminal.connect_size_allocate(glib::clone!(@weak session => move |terminal, _| {
+            // vte works out its rows and columns in its own size-allocate
+            glib::idle_add_local_once(glib::clone!(@weak session, @weak terminal, @strong id => move || {
+                session.resize(&terminal, &id);
+            }));
+        }));
+        // what's on the screen already, with its scrollback
+        self.command(
+            &format!("capture-pane -p -e -S - -t {}", id),
+            glib::clone!(@weak terminal => move |_, lines| {
+                terminal.feed(lines.join("\r\n").as_bytes());
+            }),
+        );
+        self.command(
+            &format!(
+                "display-message -p -t {} \"#{{cursor_y}} #{{cursor_x}}\"",
+                id
+            ),
+            glib::clone!(@weak terminal => move |_, lines| {
+                let cursor: Vec<i64> = lines
+                    .first()
+                    .map(|line| line.split(' ').filter_map(|n| n.parse().ok()).collect())
+                    .unwrap_or_default();
+                if let [row, column] = cursor[..] {
+                    terminal.feed(format!("\x1b[{};{}H", row + 1, column + 1).as_bytes());
+                }
+            }),
+        );
+        terminal
+    }
+
+    // tmux has one size for the whole client, the tab's, and one for each pane
+    fn resize(self: &Rc<Self>, terminal: &vte::Terminal, id: &str) {
+        let pane = (terminal.column_count(), terminal.row_count());
+        let client = split::page(terminal).map(|page| {
+            let allocation = page.allocation();
+            (
+                allocation.width as i64 / terminal.char_width().max(1),
+                allocation.height as i64 / terminal.char_height().max(1),
+            )
+        });
+        let (pane_changed, client_changed) = {
+            let mut state = self.state.borrow_mut();
+            let pane_changed = state.sizes.insert(id.to_string(), pane) != Some(pane);
+            let client_changed = match client {
+                Some(client) => state.sizes.insert(String::new(), client) != Some(client),
+                None => false,
+            };
+            (pane_changed, client_changed)
+        };
+        if let (true, Some((columns, rows))) = (client_changed, client) {
+            self.send(&format!("refresh-client -C {}x{}", columns, rows));
+        }
+        if pane_changed {
+            self.send(&format!(
+                "resize-pane -t {} -x {} -y {}",
+                id, pane.0, pane.1
+            ));
+        }
+    }
+
+    fn renamed(&self, window: &str, name: &str) {
+        let term = match self.term.upgrade() {
+            Some(term) => term,
+            None => return,
+        };
+        let terminals: Vec<vte::Terminal> = {
+            let state = self.state.borrow();
+            state
+                .windows
+                .get(window)
+                .into_iter()
+                .flatten()
+                .filter_map(|id| state.panes.get(id).cloned())
+                .collect()
+        };
+        for terminal in terminals {
+            let imp = TermImpl::from_instance(&term);
+            if let Some(meta) = imp.tabs.borrow_mut().by_terminal_mut(&terminal) {
+                meta.title = Some(name.to_string());
+            }
+            term.refresh_label(&terminal);
+        }
+    }
+
+    fn window_closed(&self, window: &str) {
+        let terminals: Vec<vte::Terminal> = {
+            let mut state = self.state.borrow_mut();
+            let panes = state.windows.remove(window).unwrap_or_default();
+            panes
+                .iter()
+                .filter_map(|id| state.panes.remove(id))
+                .collect()
+        };
+        if let Some(term) = self.term.upgrade() {
+            for terminal in terminals {
+                term.close_pane(&terminal);
+            }
+        }
+    }
+
+    // the tabs go, the session may well live on
+    fn ended(&self, message: &str) {
+        let windows: Vec<String> = {
+            let mut state = self.state.borrow_mut();
+            if std::mem::replace(&mut state.ended, true) {
+                return;
+            }
+            state.windows.keys().cloned().collect()
+        };
+        for window in windows {
+            self.window_closed(&window);
+        }
+        if let Some(term) = self.term.upgrade() {
+            term.banner(gtk::MessageType::Info, message);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn panes(layout: &Layout) -> Vec<(String, gtk::Orientation)> {
+        let mut panes = Vec::new();
+        layout.panes(gtk::Orientation::Horizontal, &mut panes);
+        panes
+    }
+
+    #[test]
+    fn parse_single_pane() {
+        let layout = Layout::parse("b25f,80x24,0,0,1").unwrap();
+        assert_eq!(layout.first(), "%1");
+        assert_eq!(
+            panes(&layout),
+            vec![("%1".to_string(), gtk::Orientation::Horizontal)]
+        );
+    }
+
+    #[test]
+    fn parse_nested_splits() {
+        let layout =
+            Layout::parse("a1b2,80x24,0,0{40x24,0,0,1,39x24,41,0[39x12,41,0,2,39x11,41,13,3]}")
+                .unwrap();
+        assert_eq!(layout.first(), "%1");
+        assert_eq!(
+            panes(&layout),
+            vec![
+                ("%1".to_string(), gtk::Orientation::Horizontal),
+                ("%2".to_string(), gtk::Orientation::Vertical),
+                ("%3".to_string(), gtk::Orientation::Vertical),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_rejects_broken_layouts() {
+        assert!(Layout::parse("").is_none());
+        assert!(Layout::parse("b25f").is_none());
+        assert!(Layout::parse("b25f,80x24,0,0").is_none());
+        assert!(Layout::parse("b25f,80x24,0,0{40x24,0,0,1").is_none());
+        assert!(Layout::parse("b25f,80x24,0,0{40x24,0,0,1]").is_none());
+        assert!(Layout::parse("b25f,80x24,0,0,1,").is_none());
+        assert!(Layout::parse("b25f,80xx24,0,0,1").is_none());
+    }
+
+    #[test]
+    fn block_ends_with_its_own_guard() {
+        let begin = guard("1363006971 2 1");
+        assert_eq!(begin, "1363006971 2");
+        assert!(closes(&begin, "1363006971 2 1"));
+        assert!(closes(&begin, "1363006971 2 0"));
+        // pane output captured in the reply
+        assert!(!closes(&begin, ""));
+        assert!(!closes(&begin, "of the line"));
+        assert!(!closes(&begin, "1363006971 3 1"));
+        assert!(!closes(&begin, "1363006970 2 1"));
+    }
+
+    #[test]
+    fn unescape_octal() {
+        assert_eq!(unescape(b"plain text"), b"plain text");
+        assert_eq!(unescape(b"a\\015\\012b"), b"a\r\nb");
+        assert_eq!(unescape(b"\\134"), b"\\");
+        assert_eq!(unescape(b"\\033[0m"), b"\x1b[0m");
+    }
+
+    #[test]
+    fn unescape_keeps_what_isnt_octal() {
+        assert_eq!(unescape(b"\\"), b"\\");
+        assert_eq!(unescape(b"\\01"), b"\\01");
+        assert_eq!(unescape(b"\\09x"), b"\\09x");
+        assert_eq!(unescape(b"end\\"), b"end\\");
+    }
+}
@@ -0,0 +1,30 @@
+// what a bug report needs: our version and commit, and the libraries we
+// actually run against rather than the ones we were built with
+
+use std::path::Path;
+
+pub fn version() -> String {
+    match option_env!("PTERM_GIT_COMMIT") {
+        Some(commit) => format!("{} ({})", env!("CARGO_PKG_VERSION"), commit),
+        None => env!("CARGO_PKG_VERSION").to_string(),
+    }
+}
+
+// the gtk ones need gtk initialized, so they're only there with a display
+pub fn details(config_path: &Path) -> String {
+    let (major, minor, micro) = crate::hacks::vte_version();
+    let mut details = vec![
+        format!("pterm {}", version()),
+        format!("vte {}.{}.{}", major, minor, micro),
+    ];
+    if gtk::is_initialized() {
+        details.push(format!(
+            "gtk {}.{}.{}",
+            gtk::major_version(),
+            gtk::minor_version(),
+            gtk::micro_version()
+        ));
+    }
+    details.push(format!("config {}", config_path.display()));
+    details.join("\n")
+}
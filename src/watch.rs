@@ -0,0 +1,242 @@
+// triggers: regexes checked against new lines of a tab as they come in, from
+// [[triggers]] in the config or watches added to a tab from the ui
+
+use std::time::{Duration, Instant};
+
+use gtk::prelude::*;
+use gtk::subclass::prelude::*;
+use regex::Regex;
+use vte::TerminalExt;
+
+use crate::{hacks, Term, TermImpl};
+
+// a flood of output is only checked this far back
+const MAX_SCAN_ROWS: i64 = 1000;
+// a trigger fires at most this often in a tab, highlighting aside
+const MIN_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone)]
+pub enum TriggerAction {
+    Highlight(gdk::RGBA),
+    Sound,
+    // copies the capture group to the clipboard, 0 is the whole match
+    Copy(usize),
+    // run with the matching line in $PTERM_MATCH
+    Run(Vec<String>),
+    // only when the tab isn't in front of the user
+    Notify,
+    // the capture group is a percentage shown in the tab and window title
+    Progress(usize),
+}
+
+#[derive(Debug, Clone)]
+pub struct Trigger {
+    pub regex: Regex,
+    pub action: TriggerAction,
+}
+
+// a watch notifies and optionally runs a hook command, quoted as in a shell
+pub fn watch(pattern: &str, hook: &str) -> Result<Vec<Trigger>, String> {
+    let regex = Regex::new(pattern).map_err(|err| err.to_string())?;
+    let hook: Vec<String> = match hook.trim() {
+        "" => Vec::new(),
+        hook => glib::shell_parse_argv(hook)
+            .map_err(|err| format!("can't parse the command: {}", err))?
+            .into_iter()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect(),
+    };
+    let mut triggers = vec![Trigger {
+        regex: regex.clone(),
+        action: TriggerAction::Notify,
+    }];
+    if !hook.is_empty() {
+        triggers.push(Trigger {
+            regex,
+            action: TriggerAction::Run(hook),
+        });
+    }
+    Ok(triggers)
+}
+
+// complete lines written since the last call with their rows, `scanned` is
+// the first row not looked at yet
+pub fn new_lines(terminal: &vte::Terminal, scanned: &mut Option<i64>) -> Vec<(i64, String)> {
+    let (_, cursor_row) = terminal.cursor_position();
+    let cursor_row = cursor_row as i64;
+    let start = scanned
+        .unwrap_or(cursor_row)
+        .max(cursor_row - MAX_SCAN_ROWS);
+    *scanned = Some(cursor_row);
+    if start >= cursor_row {
+        return Vec::new();
+    }
+    (start..cursor_row)
+        .filter_map(|row| Some((row, hacks::text_row(terminal, row)?)))
+        .collect()
+}
+
+pub fn run_hook(argv: &[String], line: &str) {
+    let launcher = gio::SubprocessLauncher::new(gio::SubprocessFlags::NONE);
+    launcher.setenv("PTERM_MATCH", line, true);
+    let argv: Vec<&std::ffi::OsStr> = argv.iter().map(|arg| arg.as_ref()).collect();
+    if let Err(err) = launcher.spawnv(&argv) {
+        log::error!("failed to run {:?}: {}", argv, err);
+    }
+}
+
+// called on contents-changed
+pub fn scan(term: &Term, terminal: &vte::Terminal) {
+    let imp = TermImpl::from_instance(term);
+    let mut triggers = imp.config.borrow().triggers.clone();
+    let lines = match imp.tabs.borrow_mut().by_terminal_mut(terminal) {
+        Some(meta) => {
+            triggers.extend(meta.watches.iter().cloned());
+            if triggers.is_empty() {
+                return;
+            }
+            new_lines(terminal, &mut meta.scanned_row)
+        }
+        None => return,
+    };
+    // nobody needs telling about what they're looking at
+    let visible = term.is_active() && term.active_terminal().as_ref() == Some(terminal);
+    // progress bars are usually redrawn in place, on the line the cursor is on
+    let (_, cursor_row) = terminal.cursor_position();
+    let current_line = triggers
+        .iter()
+        .any(|trigger| matches!(trigger.action, TriggerAction::Progress(_)))
+        .then(|| hacks::text_row(terminal, cursor_row as i64))
+        .flatten();
+    let mut progress = None;
+    for line in lines
+        .iter()
+        .map(|(_, line)| line)
+        .chain(current_line.as_ref())
+    {
+        for trigger in &triggers {
+            if let TriggerAction::Progress(group) = trigger.action {
+                let percent = trigger
+                    .regex
+                    .captures(line)
+                    .and_then(|captures| captures.get(group)?.as_str().trim().parse::<f64>().ok());
+                progress = percent.or(progress);
+            }
+        }
+    }
+    if let Some(percent) = progress {
+        set_progress(term, terminal, Some(percent.clamp(0.0, 100.0) as u8));
+    }
+
+    for (row, line) in &lines {
+        for (index, trigger) in triggers.iter().enumerate() {
+            let captures = match trigger.regex.captures(line) {
+                Some(captures) => captures,
+                None => continue,
+            };
+            if let TriggerAction::Progress(_) = &trigger.action {
+                continue;
+            }
+            if let TriggerAction::Highlight(color) = &trigger.action {
+                if let Some(meta) = imp.tabs.borrow_mut().by_terminal_mut(terminal) {
+                    meta.add_highlight(*row, *color);
+                }
+                terminal.queue_draw();
+                continue;
+            }
+            let now = Instant::now();
+            match imp.tabs.borrow_mut().by_terminal_mut(terminal) {
+                Some(meta) => {
+                    let last = meta.triggers_fired.get(&index);
+                    if last.map(|last| now - *last < MIN_INTERVAL).unwrap_or(false) {
+                        continue;
+                    }
+                    meta.triggers_fired.insert(index, now);
+                }
+                None => return,
+            }
+            match &trigger.action {
+                TriggerAction::Highlight(_) | TriggerAction::Progress(_) => {}
+                TriggerAction::Sound => terminal.error_bell(),
+                TriggerAction::Copy(group) => {
+                    if let Some(text) = captures.get(*group) {
+                        gtk::Clipboard::get(&gdk::SELECTION_CLIPBOARD).set_text(text.as_str());
+                    }
+                }
+                TriggerAction::Run(argv) => run_hook(argv, line),
+                TriggerAction::Notify => {
+                    if !visible {
+                        term.alert(terminal, &format!("{} matched", trigger.regex), line.trim());
+                    }
+                }
+            }
+        }
+    }
+}
+
+// 100% and the end of the command, with shell integration, clear it
+pub fn set_progress(term: &Term, terminal: &vte::Terminal, percent: Option<u8>) {
+    let imp = TermImpl::from_instance(term);
+    let percent = percent.filter(|percent| *percent < 100);
+    match imp.tabs.borrow_mut().by_terminal_mut(terminal) {
+        Some(meta) if meta.progress != percent => meta.progress = percent,
+        _ => return,
+    }
+    term.refresh_label(terminal);
+    term.update_title(terminal);
+}
+
+// asks for a pattern and an optional hook command for the active terminal
+pub fn prompt(term: &Term) {
+    let terminal = match term.active_terminal() {
+        Some(terminal) => terminal,
+        None => return,
+    };
+    let dialog = gtk::Dialog::with_buttons(
+        Some("Add Watch"),
+        Some(term),
+        gtk::DialogFlags::MODAL | gtk::DialogFlags::DESTROY_WITH_PARENT,
+        &[
+            ("Cancel", gtk::ResponseType::Cancel),
+            ("Add", gtk::ResponseType::Ok),
+        ],
+    );
+    dialog.set_default_response(gtk::ResponseType::Ok);
+
+    let pattern = gtk::Entry::builder()
+        .placeholder_text("regex, e.g. ERROR|panicked at")
+        .activates_default(true)
+        .build();
+    let hook = gtk::Entry::builder()
+        .placeholder_text("command to run on match (optional)")
+        .activates_default(true)
+        .build();
+    let error = gtk::Label::builder().xalign(0.0).build();
+    let content = dialog.content_area();
+    content.set_spacing(6);
+    content.pack_start(&pattern, false, false, 0);
+    content.pack_start(&hook, false, false, 0);
+    content.pack_start(&error, false, false, 0);
+
+    dialog.connect_response(
+        glib::clone!(@weak term, @weak terminal, @weak pattern, @weak hook, @weak error => move |dialog, response| {
+            if response == gtk::ResponseType::Ok {
+                match watch(&pattern.text(), &hook.text()) {
+                    Ok(triggers) => {
+                        let imp = TermImpl::from_instance(&term);
+                        if let Some(meta) = imp.tabs.borrow_mut().by_terminal_mut(&terminal) {
+                            meta.watches.extend(triggers);
+                        }
+                    }
+                    // keep the dialog open to fix the pattern or the command
+                    Err(err) => {
+                        error.set_text(&err);
+                        return;
+                    }
+                }
+            }
+            dialog.close();
+        }),
+    );
+    dialog.show_all();
+}